@@ -1,9 +1,54 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Mint, MintTo, Transfer};
+use anchor_spl::token::{self, Burn, CloseAccount, Token, TokenAccount, Mint, MintTo, Transfer};
+use me_token::{is_user_id_hash, UserMeAccount};
 use sha2::{Sha256, Digest};
+use user_pda::UserWallet;
 
 declare_id!("FhdroQrark3WFM6aSG1PpESmCXee4uvMxmYvRKD9FfTN");
 
+// User wallets are checked against user_pda::ID. Until user-pda is deployed its
+// declare_id! is the System Program placeholder, which no UserWallet can match,
+// so refuse to build a deployable program against it.
+#[cfg(target_os = "solana")]
+const _: () = {
+    let id = user_pda::ID.to_bytes();
+    let mut i = 0;
+    let mut placeholder = true;
+    while i < id.len() {
+        if id[i] != 0 {
+            placeholder = false;
+        }
+        i += 1;
+    }
+    assert!(
+        !placeholder,
+        "user-pda has no deployed program ID; deploy it and set declare_id! first"
+    );
+};
+
+const PIN_COMMITMENT_DOMAIN: &[u8] = b"withparen:pin:v1";
+
+/// Commitment stored for a connection PIN:
+/// sha256(domain || salt || connection || pin owner || pin).
+/// Same scheme as `pin_commitment` in unified-token-program, which documents
+/// why the salt is generated and kept on the PIN owner's device.
+fn pin_commitment(salt: &[u8; 32], connection: &Pubkey, owner: &Pubkey, pin: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(PIN_COMMITMENT_DOMAIN);
+    hasher.update(salt);
+    hasher.update(connection.as_ref());
+    hasher.update(owner.as_ref());
+    hasher.update(pin);
+    hasher.finalize().into()
+}
+
+/// Whether a token account owned by `owner` can be spent by `user`. Only the
+/// wallet's authority signs; the user PDA itself never can, so $ME sent to an
+/// account it owns would be stuck.
+fn can_spend(owner: &Pubkey, user: &UserWallet) -> bool {
+    *owner == user.authority
+}
+
 #[program]
 pub mod human_connection {
     use super::*;
@@ -12,31 +57,69 @@ pub mod human_connection {
     const MEMO_REWARD_AMOUNT: u64 = 8;
     const MEMO_DECIMALS: u8 = 9;
     const ME_DECIMALS: u8 = 9;
+    const MIN_CONNECTION_EXPIRY: i64 = 3600;
+    const MAX_CONNECTION_EXPIRY: i64 = 30 * 86400;
 
-    /// Initialize a connection between two users with PIN hashes
-    /// Locks 24 $ME from user A and stores PIN hashes for verification
+    /// Propose a connection from user A to user B with PIN commitments
+    /// Locks 24 $ME from user A and stores PIN commitments for verification.
+    /// The connection stays Proposed, and cannot be unlocked, until B accepts.
+    /// The policies decide where the escrow goes if A does not unlock in time
+    /// (`no_show_policy`) or nobody does (`expiry_policy`); see settle_connection.
+    /// A no-show may not be refunded to A, since A is the one who stayed away.
+    /// The $ME must come from user A's me_token wallet and mint. `user_a_id` must
+    /// be user A's "platform:user_id", which `user_a_id_hash` is checked against;
+    /// the legacy unprefixed hash is accepted for accounts registered before it.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_connection(
         ctx: Context<InitConnection>,
         connection_id: String,
-        connection_id_hash: [u8; 32],
+        _connection_id_hash: [u8; 32],
         user_a_id: String,
         user_b_id: String,
-        pin_a_hash: [u8; 32],  // SHA256 hash of PIN A
-        pin_b_hash: [u8; 32],  // SHA256 hash of PIN B
+        user_a_id_hash: [u8; 32],
+        pin_a_hash: [u8; 32],  // Salted commitment of PIN A
+        pin_b_hash: [u8; 32],  // Salted commitment of PIN B
+        expiry_seconds: i64,
+        no_show_policy: EscrowPolicy,
+        expiry_policy: EscrowPolicy,
     ) -> Result<()> {
+        require!(
+            (MIN_CONNECTION_EXPIRY..=MAX_CONNECTION_EXPIRY).contains(&expiry_seconds),
+            ErrorCode::InvalidExpiry
+        );
+        require!(
+            no_show_policy != EscrowPolicy::RefundUserA,
+            ErrorCode::InvalidNoShowPolicy
+        );
+
+        // Tie the me_token registration to user A's wallet, not just to user_a_id
+        let user_a_pda = &ctx.accounts.user_a_pda;
+        let user_a_qualified_id = format!("{}:{}", user_a_pda.platform, user_a_pda.user_id);
+        require!(user_a_id == user_a_qualified_id, ErrorCode::UserIdMismatch);
+        require!(
+            is_user_id_hash(&user_a_qualified_id, &user_a_id_hash),
+            ErrorCode::InvalidUserIdHash
+        );
+
         let connection = &mut ctx.accounts.connection;
         let clock = Clock::get()?;
 
-        connection.connection_id = connection_id.clone();
+        connection.connection_id = connection_id;
         connection.user_a = ctx.accounts.user_a_pda.key();
         connection.user_b = ctx.accounts.user_b_pda.key();
-        connection.user_a_id = user_a_id.clone();
-        connection.user_b_id = user_b_id.clone();
+        connection.user_a_id = user_a_id;
+        connection.user_b_id = user_b_id;
         connection.pin_a_hash = pin_a_hash;
         connection.pin_b_hash = pin_b_hash;
         connection.user_a_unlocked = false;
         connection.user_b_unlocked = false;
         connection.created_at = clock.unix_timestamp;
+        connection.user_a_me_wallet = ctx.accounts.user_a_me_wallet.key();
+        connection.expires_at = clock.unix_timestamp + expiry_seconds;
+        connection.payer = ctx.accounts.payer.key();
+        connection.no_show_policy = no_show_policy;
+        connection.expiry_policy = expiry_policy;
+        connection.state = ConnectionState::Proposed;
         connection.bump = ctx.bumps.connection;
 
         // Transfer 24 $ME from user A to escrow
@@ -52,33 +135,106 @@ pub mod human_connection {
             ME_LOCK_AMOUNT * 10u64.pow(ME_DECIMALS as u32),
         )?;
 
-        msg!("Connection {} initialized with 24 $ME locked from {}",
-             connection_id, user_a_id);
+        emit!(ConnectionInitialized {
+            connection: connection.key(),
+            user_a: connection.user_a,
+            user_b: connection.user_b,
+            me_locked: ME_LOCK_AMOUNT,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// User B accepts a proposed connection, making it unlockable. B accepts the
+    /// escrow policies as proposed, so a no-show refund to A is refused here too.
+    pub fn accept_connection(
+        ctx: Context<AcceptConnection>,
+        _connection_id: String,
+        _connection_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let connection = &mut ctx.accounts.connection;
+        require!(
+            Clock::get()?.unix_timestamp < connection.expires_at,
+            ErrorCode::ConnectionExpired
+        );
+        connection.transition(ConnectionState::Accepted)?;
+
+        emit!(ConnectionAccepted {
+            connection: connection.key(),
+            user_b: connection.user_b,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// User B declines a proposed connection. User A's escrowed $ME is
+    /// returned and the connection is cancelled.
+    pub fn decline_connection(
+        ctx: Context<DeclineConnection>,
+        _connection_id: String,
+        connection_id_hash: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.connection.transition(ConnectionState::Cancelled)?;
+
+        let seeds = &[
+            b"connection".as_ref(),
+            connection_id_hash.as_ref(),
+            &[ctx.accounts.connection.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let refunded = ctx.accounts.escrow_me_wallet.amount;
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.escrow_me_wallet.to_account_info(),
+                    to: ctx.accounts.user_a_me_wallet.to_account_info(),
+                    authority: ctx.accounts.connection.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            refunded,
+        )?;
+
+        emit!(ConnectionDeclined {
+            connection: ctx.accounts.connection.key(),
+            user_b: ctx.accounts.connection.user_b,
+            me_refunded: refunded,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
         Ok(())
     }
 
-    /// User submits the OTHER person's PIN to unlock their $MEMO reward
-    /// Contract hashes the submitted PIN and compares with stored hash
+    /// User submits the OTHER person's PIN and its salt to unlock their $MEMO reward
+    /// Contract recomputes the PIN commitment and compares with the stored one
     pub fn unlock_with_pin(
         ctx: Context<UnlockWithPin>,
-        connection_id: String,
-        connection_id_hash: [u8; 32],
+        _connection_id: String,
+        _connection_id_hash: [u8; 32],
         submitted_pin: String,
+        salt: [u8; 32],
     ) -> Result<()> {
+        let connection_key = ctx.accounts.connection.key();
         let connection = &mut ctx.accounts.connection;
         let user_pubkey = ctx.accounts.user_pda.key();
 
-        // Hash the submitted PIN using SHA256
-        let mut hasher = Sha256::new();
-        hasher.update(submitted_pin.as_bytes());
-        let result = hasher.finalize();
-        let submitted_hash: [u8; 32] = result.into();
+        require!(
+            Clock::get()?.unix_timestamp < connection.expires_at,
+            ErrorCode::ConnectionExpired
+        );
 
         // Determine which user is unlocking
         let is_user_a = user_pubkey == connection.user_a;
 
         if is_user_a {
             // User A submits User B's PIN
+            let submitted_hash = pin_commitment(
+                &salt,
+                &connection_key,
+                &connection.user_b,
+                submitted_pin.as_bytes(),
+            );
             require!(
                 submitted_hash == connection.pin_b_hash,
                 ErrorCode::InvalidPin
@@ -111,14 +267,19 @@ pub mod human_connection {
                 MEMO_REWARD_AMOUNT * 10u64.pow(MEMO_DECIMALS as u32),
             )?;
 
-            msg!("User A ({}) unlocked with correct PIN! Received {} $MEMO",
-                 connection.user_a_id, MEMO_REWARD_AMOUNT);
+
         } else {
             // User B submits User A's PIN
             require!(
                 user_pubkey == connection.user_b,
                 ErrorCode::UnauthorizedUser
             );
+            let submitted_hash = pin_commitment(
+                &salt,
+                &connection_key,
+                &connection.user_a,
+                submitted_pin.as_bytes(),
+            );
             require!(
                 submitted_hash == connection.pin_a_hash,
                 ErrorCode::InvalidPin
@@ -151,12 +312,24 @@ pub mod human_connection {
                 MEMO_REWARD_AMOUNT * 10u64.pow(MEMO_DECIMALS as u32),
             )?;
 
-            msg!("User B ({}) unlocked with correct PIN! Received {} $MEMO",
-                 connection.user_b_id, MEMO_REWARD_AMOUNT);
         }
 
-        // If both users have unlocked, mint 8 $MEMO to agent
         if connection.user_a_unlocked && connection.user_b_unlocked {
+            connection.transition(ConnectionState::Completed)?;
+        } else {
+            connection.transition(ConnectionState::PartiallyUnlocked)?;
+        }
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        emit!(ConnectionUnlocked {
+            connection: connection_key,
+            user: user_pubkey,
+            memo_reward: MEMO_REWARD_AMOUNT,
+            timestamp,
+        });
+
+        // If both users have unlocked, mint 8 $MEMO to agent
+        if connection.state == ConnectionState::Completed {
             let memo_mint_bump = ctx.bumps.memo_mint;
             let seeds = &[
                 b"memo_mint".as_ref(),
@@ -177,34 +350,240 @@ pub mod human_connection {
                 MEMO_REWARD_AMOUNT * 10u64.pow(MEMO_DECIMALS as u32),
             )?;
 
-            msg!("Both users unlocked! Agent received {} $MEMO. Connection {} complete!",
-                 MEMO_REWARD_AMOUNT, connection_id);
+            emit!(ConnectionCompleted {
+                connection: connection_key,
+                user_a: connection.user_a,
+                user_b: connection.user_b,
+                agent: ctx.accounts.agent.key(),
+                agent_reward: MEMO_REWARD_AMOUNT,
+                timestamp,
+            });
         }
 
         Ok(())
     }
+
+    /// Release the escrowed $ME, then close the escrow and refund its rent to
+    /// the payer. Permissionless.
+    /// - Completed: returned to user A (a declined connection is just closed)
+    /// - After expiry, only A unlocked or B never accepted: returned to user A
+    /// - After expiry, only B unlocked: routed by the connection's no_show_policy
+    /// - After expiry, nobody unlocked: routed by the connection's expiry_policy
+    pub fn settle_connection(
+        ctx: Context<SettleConnection>,
+        _connection_id: String,
+        connection_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let connection = &mut ctx.accounts.connection;
+
+        let policy = match connection.state {
+            ConnectionState::Completed | ConnectionState::Cancelled => EscrowPolicy::RefundUserA,
+            state => {
+                require!(
+                    clock.unix_timestamp >= connection.expires_at,
+                    ErrorCode::ConnectionNotExpired
+                );
+                let policy = if state == ConnectionState::Proposed || connection.user_a_unlocked {
+                    EscrowPolicy::RefundUserA
+                } else if connection.user_b_unlocked {
+                    connection.no_show_policy
+                } else {
+                    connection.expiry_policy
+                };
+                connection.transition(ConnectionState::Expired)?;
+                policy
+            }
+        };
+
+        let seeds = &[
+            b"connection".as_ref(),
+            connection_id_hash.as_ref(),
+            &[ctx.accounts.connection.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let amount = ctx.accounts.escrow_me_wallet.amount;
+        if amount > 0 {
+            match policy {
+                EscrowPolicy::RefundUserA | EscrowPolicy::PayUserB => {
+                    let to = if policy == EscrowPolicy::PayUserB {
+                        ctx.accounts
+                            .user_b_me_wallet
+                            .as_ref()
+                            .ok_or(ErrorCode::MissingRecipient)?
+                            .to_account_info()
+                    } else {
+                        ctx.accounts.user_a_me_wallet.to_account_info()
+                    };
+                    token::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Transfer {
+                                from: ctx.accounts.escrow_me_wallet.to_account_info(),
+                                to,
+                                authority: ctx.accounts.connection.to_account_info(),
+                            },
+                            signer_seeds,
+                        ),
+                        amount,
+                    )?;
+                }
+                EscrowPolicy::Burn => {
+                    token::burn(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.token_program.to_account_info(),
+                            Burn {
+                                mint: ctx.accounts.me_mint.to_account_info(),
+                                from: ctx.accounts.escrow_me_wallet.to_account_info(),
+                                authority: ctx.accounts.connection.to_account_info(),
+                            },
+                            signer_seeds,
+                        ),
+                        amount,
+                    )?;
+                }
+            }
+        }
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_me_wallet.to_account_info(),
+                destination: ctx.accounts.payer.to_account_info(),
+                authority: ctx.accounts.connection.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        emit!(ConnectionSettled {
+            connection: ctx.accounts.connection.key(),
+            state: ctx.accounts.connection.state,
+            policy,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Release the escrow of a connection created before lifecycle states
+    /// existed. Those connections can no longer be unlocked and their escrow
+    /// was never releasable, so the $ME always goes back to the me_token wallet
+    /// of the escrow's mint. The legacy layout never recorded a payer, so the
+    /// escrow and connection rent go to that wallet's owner. Permissionless.
+    pub fn settle_legacy_connection(
+        ctx: Context<SettleLegacyConnection>,
+        connection_id_hash: [u8; 32],
+        _user_a_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let connection_info = ctx.accounts.connection.to_account_info();
+        require!(
+            connection_info.data_len() == LegacyConnection::SPACE,
+            ErrorCode::NotLegacyConnection
+        );
+        let legacy = {
+            let data = connection_info.try_borrow_data()?;
+            require!(
+                data[..8] == *Connection::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            LegacyConnection::deserialize(&mut &data[8..])?
+        };
+
+        let seeds = &[
+            b"connection".as_ref(),
+            connection_id_hash.as_ref(),
+            &[ctx.bumps.connection],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let amount = ctx.accounts.escrow_me_wallet.amount;
+        if amount > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.escrow_me_wallet.to_account_info(),
+                        to: ctx.accounts.user_a_me_wallet.to_account_info(),
+                        authority: connection_info.clone(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+            )?;
+        }
+
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow_me_wallet.to_account_info(),
+                destination: ctx.accounts.rent_recipient.to_account_info(),
+                authority: connection_info.clone(),
+            },
+            signer_seeds,
+        ))?;
+
+        // Close the legacy connection account itself
+        let recipient = ctx.accounts.rent_recipient.to_account_info();
+        **recipient.try_borrow_mut_lamports()? += connection_info.lamports();
+        **connection_info.try_borrow_mut_lamports()? = 0;
+        connection_info.assign(&anchor_lang::system_program::ID);
+        connection_info.resize(0)?;
+
+        emit!(LegacyConnectionSettled {
+            connection: connection_info.key(),
+            user_a: legacy.user_a,
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
-#[instruction(connection_id: String, connection_id_hash: [u8; 32], user_a_id: String, user_b_id: String)]
+#[instruction(connection_id: String, connection_id_hash: [u8; 32], user_a_id: String, user_b_id: String, user_a_id_hash: [u8; 32])]
 pub struct InitConnection<'info> {
     #[account(
         init,
         payer = payer,
-        space = 8 + 4 + 64 + 32 + 32 + 4 + 64 + 4 + 64 + 32 + 32 + 1 + 1 + 8 + 1,
+        space = 8 + 4 + 64 + 32 + 32 + 4 + 64 + 4 + 64 + 32 + 32 + 1 + 1 + 8 + 32 + 8 + 32 + 1 + 1 + 1 + 1,
         seeds = [b"connection", connection_id_hash.as_ref()],
         bump
     )]
     pub connection: Account<'info, Connection>,
 
-    /// CHECK: User A PDA (validated via constraints)
-    pub user_a_pda: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"user", user_a_pda.platform.as_bytes(), user_a_pda.user_id.as_bytes()],
+        bump = user_a_pda.bump,
+        seeds::program = user_pda::ID
+    )]
+    pub user_a_pda: Account<'info, UserWallet>,
 
-    /// CHECK: User B PDA (validated via constraints)
-    pub user_b_pda: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"user", user_b_pda.platform.as_bytes(), user_b_pda.user_id.as_bytes()],
+        bump = user_b_pda.bump,
+        seeds::program = user_pda::ID,
+        constraint = user_b_pda.key() != user_a_pda.key() @ ErrorCode::SameUserConnection
+    )]
+    pub user_b_pda: Account<'info, UserWallet>,
 
-    #[account(mut)]
-    pub user_a_me_wallet: Account<'info, TokenAccount>,
+    /// User A's me_token registration, recording their ME mint
+    #[account(
+        seeds = [b"user_me", user_a_id_hash.as_ref()],
+        bump = user_a_me_account.bump,
+        seeds::program = me_token::ID
+    )]
+    pub user_a_me_account: Box<Account<'info, UserMeAccount>>,
+
+    /// User A's me_token ME wallet
+    #[account(
+        mut,
+        seeds = [b"me_wallet", user_a_id_hash.as_ref()],
+        bump,
+        seeds::program = me_token::ID,
+        token::mint = user_a_me_mint
+    )]
+    pub user_a_me_wallet: Box<Account<'info, TokenAccount>>,
 
     #[account(
         init,
@@ -214,11 +593,16 @@ pub struct InitConnection<'info> {
         seeds = [b"escrow", connection_id_hash.as_ref()],
         bump
     )]
-    pub escrow_me_wallet: Account<'info, TokenAccount>,
+    pub escrow_me_wallet: Box<Account<'info, TokenAccount>>,
 
+    #[account(address = user_a_me_account.me_mint @ ErrorCode::InvalidMeMint)]
     pub user_a_me_mint: Account<'info, Mint>,
 
     /// User A's authority (signer for token transfer)
+    #[account(
+        address = user_a_pda.authority @ ErrorCode::UnauthorizedAuthority,
+        constraint = user_a_authority.key() == user_a_me_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
     pub user_a_authority: Signer<'info>,
 
     #[account(mut)]
@@ -229,6 +613,71 @@ pub struct InitConnection<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(connection_id: String, connection_id_hash: [u8; 32])]
+pub struct AcceptConnection<'info> {
+    #[account(
+        mut,
+        seeds = [b"connection", connection_id_hash.as_ref()],
+        bump = connection.bump,
+        constraint = connection.state == ConnectionState::Proposed @ ErrorCode::InvalidConnectionState,
+        constraint = connection.no_show_policy != EscrowPolicy::RefundUserA @ ErrorCode::InvalidNoShowPolicy
+    )]
+    pub connection: Account<'info, Connection>,
+
+    #[account(
+        seeds = [b"user", user_b_pda.platform.as_bytes(), user_b_pda.user_id.as_bytes()],
+        bump = user_b_pda.bump,
+        seeds::program = user_pda::ID,
+        address = connection.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_pda: Account<'info, UserWallet>,
+
+    /// User B's authority, consenting to the connection
+    #[account(address = user_b_pda.authority @ ErrorCode::UnauthorizedAuthority)]
+    pub user_b_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(connection_id: String, connection_id_hash: [u8; 32])]
+pub struct DeclineConnection<'info> {
+    #[account(
+        mut,
+        seeds = [b"connection", connection_id_hash.as_ref()],
+        bump = connection.bump,
+        constraint = connection.state == ConnectionState::Proposed @ ErrorCode::InvalidConnectionState
+    )]
+    pub connection: Account<'info, Connection>,
+
+    #[account(
+        seeds = [b"user", user_b_pda.platform.as_bytes(), user_b_pda.user_id.as_bytes()],
+        bump = user_b_pda.bump,
+        seeds::program = user_pda::ID,
+        address = connection.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_pda: Account<'info, UserWallet>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", connection_id_hash.as_ref()],
+        bump
+    )]
+    pub escrow_me_wallet: Account<'info, TokenAccount>,
+
+    /// User A's $ME wallet the escrow was funded from
+    #[account(
+        mut,
+        address = connection.user_a_me_wallet
+    )]
+    pub user_a_me_wallet: Account<'info, TokenAccount>,
+
+    /// User B's authority, declining the connection
+    #[account(address = user_b_pda.authority @ ErrorCode::UnauthorizedAuthority)]
+    pub user_b_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 #[instruction(connection_id: String, connection_id_hash: [u8; 32])]
 pub struct UnlockWithPin<'info> {
@@ -236,11 +685,24 @@ pub struct UnlockWithPin<'info> {
         mut,
         seeds = [b"connection", connection_id_hash.as_ref()],
         bump = connection.bump,
+        constraint = matches!(
+            connection.state,
+            ConnectionState::Accepted | ConnectionState::PartiallyUnlocked
+        ) @ ErrorCode::InvalidConnectionState
     )]
     pub connection: Account<'info, Connection>,
 
-    /// CHECK: User PDA submitting PIN
-    pub user_pda: UncheckedAccount<'info>,
+    /// User PDA submitting PIN
+    #[account(
+        seeds = [b"user", user_pda.platform.as_bytes(), user_pda.user_id.as_bytes()],
+        bump = user_pda.bump,
+        seeds::program = user_pda::ID
+    )]
+    pub user_pda: Account<'info, UserWallet>,
+
+    /// The user PDA's authority
+    #[account(address = user_pda.authority @ ErrorCode::UnauthorizedAuthority)]
+    pub authority: Signer<'info>,
 
     #[account(
         init_if_needed,
@@ -252,12 +714,15 @@ pub struct UnlockWithPin<'info> {
     )]
     pub memo_mint: Account<'info, Mint>,
 
+    /// Owned by the wallet's authority, since the user PDA cannot sign to move
+    /// $MEMO out. Wallets under the old ["memo_wallet", user_pda] seeds were
+    /// owned by the PDA, so new ones use a different seed.
     #[account(
         init_if_needed,
         payer = payer,
         token::mint = memo_mint,
-        token::authority = user_pda,
-        seeds = [b"memo_wallet", user_pda.key().as_ref()],
+        token::authority = authority,
+        seeds = [b"user_memo_wallet", user_pda.key().as_ref()],
         bump
     )]
     pub user_memo_wallet: Account<'info, TokenAccount>,
@@ -283,6 +748,112 @@ pub struct UnlockWithPin<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+#[instruction(connection_id: String, connection_id_hash: [u8; 32])]
+pub struct SettleConnection<'info> {
+    #[account(
+        mut,
+        seeds = [b"connection", connection_id_hash.as_ref()],
+        bump = connection.bump,
+        constraint = matches!(
+            connection.state,
+            ConnectionState::Proposed
+                | ConnectionState::Accepted
+                | ConnectionState::PartiallyUnlocked
+                | ConnectionState::Completed
+                | ConnectionState::Cancelled
+        ) @ ErrorCode::InvalidConnectionState
+    )]
+    pub connection: Account<'info, Connection>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", connection_id_hash.as_ref()],
+        bump
+    )]
+    pub escrow_me_wallet: Account<'info, TokenAccount>,
+
+    /// User A's $ME mint, burned from under EscrowPolicy::Burn
+    #[account(
+        mut,
+        address = escrow_me_wallet.mint
+    )]
+    pub me_mint: Account<'info, Mint>,
+
+    /// User A's $ME wallet the escrow was funded from
+    #[account(
+        mut,
+        address = connection.user_a_me_wallet
+    )]
+    pub user_a_me_wallet: Account<'info, TokenAccount>,
+
+    /// User B's wallet, whose authority receives a PayUserB payout
+    #[account(
+        seeds = [b"user", user_b_pda.platform.as_bytes(), user_b_pda.user_id.as_bytes()],
+        bump = user_b_pda.bump,
+        seeds::program = user_pda::ID,
+        address = connection.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_pda: Account<'info, UserWallet>,
+
+    /// User B's account for user A's $ME, only needed under EscrowPolicy::PayUserB
+    #[account(
+        mut,
+        token::mint = me_mint,
+        constraint = can_spend(&user_b_me_wallet.owner, &user_b_pda) @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_me_wallet: Option<Account<'info, TokenAccount>>,
+
+    /// CHECK: Receives the escrow rent, must be the connection's payer
+    #[account(
+        mut,
+        address = connection.payer
+    )]
+    pub payer: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(connection_id_hash: [u8; 32], user_a_id_hash: [u8; 32])]
+pub struct SettleLegacyConnection<'info> {
+    /// CHECK: Legacy layout; the handler checks size and discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"connection", connection_id_hash.as_ref()],
+        bump
+    )]
+    pub connection: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"escrow", connection_id_hash.as_ref()],
+        bump,
+        token::authority = connection
+    )]
+    pub escrow_me_wallet: Account<'info, TokenAccount>,
+
+    /// The me_token wallet registered for the escrow's mint
+    #[account(
+        mut,
+        seeds = [b"me_wallet", user_a_id_hash.as_ref()],
+        bump,
+        seeds::program = me_token::ID,
+        token::mint = escrow_me_wallet.mint
+    )]
+    pub user_a_me_wallet: Account<'info, TokenAccount>,
+
+    /// CHECK: Receives the rent, must own user A's ME wallet
+    #[account(
+        mut,
+        address = user_a_me_wallet.owner
+    )]
+    pub rent_recipient: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
 #[account]
 pub struct Connection {
     pub connection_id: String,       // Unique connection ID (max 64 bytes)
@@ -290,14 +861,145 @@ pub struct Connection {
     pub user_b: Pubkey,              // User B PDA (32 bytes)
     pub user_a_id: String,           // User A ID string (max 64 bytes)
     pub user_b_id: String,           // User B ID string (max 64 bytes)
-    pub pin_a_hash: [u8; 32],        // SHA256 hash of PIN A (32 bytes)
-    pub pin_b_hash: [u8; 32],        // SHA256 hash of PIN B (32 bytes)
+    pub pin_a_hash: [u8; 32],        // Salted commitment of PIN A (32 bytes)
+    pub pin_b_hash: [u8; 32],        // Salted commitment of PIN B (32 bytes)
     pub user_a_unlocked: bool,       // User A unlock status (1 byte)
     pub user_b_unlocked: bool,       // User B unlock status (1 byte)
     pub created_at: i64,             // Creation timestamp (8 bytes)
+    pub user_a_me_wallet: Pubkey,    // Wallet the escrowed $ME came from (32 bytes)
+    pub expires_at: i64,             // Unlock deadline (8 bytes)
+    pub payer: Pubkey,               // Paid the escrow rent, refunded on settle (32 bytes)
+    pub no_show_policy: EscrowPolicy, // Escrow routing when only B unlocked (1 byte)
+    pub expiry_policy: EscrowPolicy, // Escrow routing when nobody unlocked (1 byte)
+    pub state: ConnectionState,      // Lifecycle state (1 byte)
     pub bump: u8,                    // PDA bump seed (1 byte)
 }
 
+impl Connection {
+    /// Move to `next`, rejecting anything ConnectionState::can_transition_to does not allow
+    fn transition(&mut self, next: ConnectionState) -> Result<()> {
+        require!(
+            self.state.can_transition_to(next),
+            ErrorCode::InvalidStateTransition
+        );
+        self.state = next;
+        Ok(())
+    }
+}
+
+/// Connection as first deployed, before expiry, policies and states.
+/// Shares the Connection discriminator; only read by settle_legacy_connection.
+#[derive(AnchorDeserialize)]
+pub struct LegacyConnection {
+    pub connection_id: String,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub user_a_id: String,
+    pub user_b_id: String,
+    pub pin_a_hash: [u8; 32],
+    pub pin_b_hash: [u8; 32],
+    pub user_a_unlocked: bool,
+    pub user_b_unlocked: bool,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl LegacyConnection {
+    pub const SPACE: usize = 8 + 4 + 64 + 32 + 32 + 4 + 64 + 4 + 64 + 32 + 32 + 1 + 1 + 8 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Proposed,          // Created, waiting for user B to accept
+    Accepted,          // User B agreed, PINs unlockable
+    PartiallyUnlocked, // One user has unlocked
+    Completed,         // Both users have unlocked
+    Expired,           // Settled after the deadline
+    Cancelled,         // Declined by user B
+}
+
+impl ConnectionState {
+    /// The single source of truth for the connection lifecycle. Only the
+    /// states this program's instructions reach; scheduling and disputes
+    /// live in the unified program (see "Connection States" in the README).
+    fn can_transition_to(self, next: ConnectionState) -> bool {
+        use ConnectionState::*;
+        matches!(
+            (self, next),
+            (Proposed, Accepted | Cancelled | Expired)
+                | (Accepted, PartiallyUnlocked | Expired)
+                | (PartiallyUnlocked, Completed | Expired)
+        )
+    }
+}
+
+/// Where settle_connection sends user A's escrowed $ME
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscrowPolicy {
+    RefundUserA,       // Return it to user A's wallet
+    PayUserB,          // Pay it to user B
+    Burn,              // Burn it
+}
+
+#[event]
+pub struct ConnectionInitialized {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub me_locked: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionAccepted {
+    pub connection: Pubkey,
+    pub user_b: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionDeclined {
+    pub connection: Pubkey,
+    pub user_b: Pubkey,
+    pub me_refunded: u64,            // $ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionUnlocked {
+    pub connection: Pubkey,
+    pub user: Pubkey,
+    pub memo_reward: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionCompleted {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub agent: Pubkey,
+    pub agent_reward: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionSettled {
+    pub connection: Pubkey,
+    pub state: ConnectionState,
+    pub policy: EscrowPolicy,
+    pub amount: u64,                 // $ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LegacyConnectionSettled {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Invalid PIN provided")]
@@ -311,4 +1013,75 @@ pub enum ErrorCode {
 
     #[msg("Connection ID too long")]
     ConnectionIdTooLong,
+
+    #[msg("Connection is not in a state that allows this instruction")]
+    InvalidConnectionState,
+
+    #[msg("Connection state transition not allowed")]
+    InvalidStateTransition,
+
+    #[msg("Expiry window out of range (1 hour to 30 days)")]
+    InvalidExpiry,
+
+    #[msg("Connection has expired")]
+    ConnectionExpired,
+
+    #[msg("Connection has not reached its expiry deadline")]
+    ConnectionNotExpired,
+
+    #[msg("User B's $ME wallet is required by the escrow policy")]
+    MissingRecipient,
+
+    #[msg("Signer is not the authority of this user")]
+    UnauthorizedAuthority,
+
+    #[msg("Cannot create connection with same user")]
+    SameUserConnection,
+
+    #[msg("User ID hash does not match the user ID")]
+    InvalidUserIdHash,
+
+    #[msg("ME mint is not user A's registered me_token mint")]
+    InvalidMeMint,
+
+    #[msg("User ID does not match user A's wallet")]
+    UserIdMismatch,
+
+    #[msg("A no-show cannot be refunded to user A")]
+    InvalidNoShowPolicy,
+
+    #[msg("Connection does not use the legacy layout")]
+    NotLegacyConnection,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_wallet(authority: Pubkey) -> UserWallet {
+        UserWallet {
+            platform: "telegram".to_string(),
+            user_id: "bob456".to_string(),
+            authority,
+            created_at: 0,
+            updated_at: 0,
+            metadata: String::new(),
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn pay_user_b_goes_to_an_account_b_can_spend() {
+        let authority = Pubkey::new_unique();
+        let user_b = user_wallet(authority);
+        let (user_b_pda, _) = Pubkey::find_program_address(
+            &[b"user", user_b.platform.as_bytes(), user_b.user_id.as_bytes()],
+            &user_pda::ID,
+        );
+
+        assert!(can_spend(&authority, &user_b));
+        // connection.user_b is the PDA, which cannot sign a transfer out
+        assert!(!can_spend(&user_b_pda, &user_b));
+        assert!(!can_spend(&Pubkey::new_unique(), &user_b));
+    }
 }
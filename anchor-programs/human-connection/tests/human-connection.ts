@@ -0,0 +1,852 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program, BN, LangErrorCode } from "@coral-xyz/anchor";
+import { BankrunProvider } from "anchor-bankrun";
+import { startAnchor, Clock, ProgramTestContext } from "solana-bankrun";
+import { HumanConnection } from "../target/types/human_connection";
+import { MeToken } from "../../me-token/target/types/me_token";
+import { UserPda } from "../../user-pda/target/types/user_pda";
+import {
+  PublicKey,
+  Keypair,
+  SystemProgram,
+  LAMPORTS_PER_SOL,
+  SYSVAR_RENT_PUBKEY
+} from "@solana/web3.js";
+import {
+  TOKEN_PROGRAM_ID,
+  AccountLayout,
+  MintLayout,
+  ACCOUNT_SIZE,
+  MINT_SIZE
+} from "@solana/spl-token";
+import { createHash, randomBytes } from "crypto";
+import { assert } from "chai";
+
+// Runs on bankrun rather than a local validator: settling needs the clock moved
+// past deadlines of at least an hour, and legacy-layout accounts written
+// directly. Build me-token and user-pda first; Anchor.toml loads their programs.
+describe("human-connection", () => {
+  let context: ProgramTestContext;
+  let provider: BankrunProvider;
+  let program: Program<HumanConnection>;
+  let meToken: Program<MeToken>;
+  let userPda: Program<UserPda>;
+
+  // Admin pays fees, is the custodial authority of most users and is the agent
+  let admin: Keypair;
+  // Pays connection and escrow rent, so settle refunds can be measured exactly
+  const rentPayer = Keypair.generate();
+
+  const platform = "telegram";
+  const policies = {
+    refundUserA: { refundUserA: {} },
+    payUserB: { payUserB: {} },
+    burn: { burn: {} },
+  };
+
+  interface User {
+    qualifiedId: string;
+    idHash: Buffer;
+    pda: PublicKey;
+    authority: Keypair;
+    meAccount: PublicKey;
+    meMint: PublicKey;
+    meWallet: PublicKey;
+  }
+
+  interface Proposal {
+    id: string;
+    idHash: Buffer;
+    connection: PublicKey;
+    escrow: PublicKey;
+    userA: User;
+    userB: User;
+    pinA: string;
+    saltA: Buffer;
+    pinB: string;
+    saltB: Buffer;
+  }
+
+  // Helper function: Hash user ID under the me-token domain prefix
+  function hashUserId(userId: string): Buffer {
+    return createHash('sha256').update("withparen:user_id:v1:").update(userId).digest();
+  }
+
+  // Helper function: Hash a connection ID into its PDA seed
+  function hashConnectionId(connectionId: string): Buffer {
+    return createHash('sha256').update(connectionId).digest();
+  }
+
+  // Helper function: Salted PIN commitment bound to the connection and PIN owner
+  function commitPin(salt: Buffer, connection: PublicKey, owner: PublicKey, pin: string): Buffer {
+    return createHash('sha256')
+      .update("withparen:pin:v1")
+      .update(salt)
+      .update(connection.toBuffer())
+      .update(owner.toBuffer())
+      .update(pin)
+      .digest();
+  }
+
+  // Helper function: Derive a PDA of the given program
+  function derivePDA(seeds: Buffer[], programId: PublicKey): PublicKey {
+    return PublicKey.findProgramAddressSync(seeds, programId)[0];
+  }
+
+  // Helper function: Signers for a transaction, leaving out the provider wallet
+  function signersOf(...keypairs: Keypair[]): Keypair[] {
+    return keypairs.filter((keypair) => !keypair.publicKey.equals(admin.publicKey));
+  }
+
+  // Helper function: Give a keypair SOL for rent and fees
+  function fund(keypair: Keypair) {
+    context.setAccount(keypair.publicKey, {
+      lamports: 10 * LAMPORTS_PER_SOL,
+      data: Buffer.alloc(0),
+      owner: SystemProgram.programId,
+      executable: false,
+    });
+  }
+
+  // Helper function: Write an initialized token account holding `amount` base units
+  async function setTokenAccount(address: PublicKey, mint: PublicKey, owner: PublicKey, amount: bigint) {
+    const data = Buffer.alloc(ACCOUNT_SIZE);
+    AccountLayout.encode(
+      {
+        mint,
+        owner,
+        amount,
+        delegateOption: 0,
+        delegate: PublicKey.default,
+        state: 1,
+        isNativeOption: 0,
+        isNative: BigInt(0),
+        delegatedAmount: BigInt(0),
+        closeAuthorityOption: 0,
+        closeAuthority: PublicKey.default,
+      },
+      data
+    );
+    const rent = await context.banksClient.getRent();
+    context.setAccount(address, {
+      lamports: Number(rent.minimumBalance(BigInt(ACCOUNT_SIZE))),
+      data,
+      owner: TOKEN_PROGRAM_ID,
+      executable: false,
+    });
+  }
+
+  // Helper function: Token balance in whole tokens
+  async function balanceOf(tokenAccount: PublicKey): Promise<number> {
+    const account = await context.banksClient.getAccount(tokenAccount);
+    return Number(AccountLayout.decode(account.data).amount) / 1e9;
+  }
+
+  // Helper function: Mint supply in whole tokens
+  async function supplyOf(mint: PublicKey): Promise<number> {
+    const account = await context.banksClient.getAccount(mint);
+    return Number(MintLayout.decode(account.data).supply) / 1e9;
+  }
+
+  // Helper function: SOL balance in lamports
+  async function lamportsOf(address: PublicKey): Promise<number> {
+    return Number(await context.banksClient.getBalance(address));
+  }
+
+  // Helper function: Advance one slot so a retried transaction gets a fresh blockhash
+  async function nextSlot() {
+    const clock = await context.banksClient.getClock();
+    context.warpToSlot(clock.slot + BigInt(1));
+  }
+
+  // Helper function: Move the clock past the connection's unlock deadline
+  async function warpPast(connection: PublicKey) {
+    const { expiresAt } = await program.account.connection.fetch(connection);
+    await nextSlot();
+    const clock = await context.banksClient.getClock();
+    context.setClock(
+      new Clock(
+        clock.slot,
+        clock.epochStartTimestamp,
+        clock.epoch,
+        clock.leaderScheduleEpoch,
+        BigInt(expiresAt.toString()) + BigInt(1)
+      )
+    );
+  }
+
+  // Helper function: Assert a transaction fails with the named program or Anchor
+  // error. Bankrun reports errors by code, without program logs.
+  async function expectError(tx: Promise<unknown>, name: string) {
+    const idlError = program.idl.errors.find((e) => e.name.toLowerCase() === name.toLowerCase());
+    const code = idlError ? idlError.code : LangErrorCode[name as keyof typeof LangErrorCode];
+    assert.isDefined(code, `unknown error ${name}`);
+
+    try {
+      await tx;
+    } catch (error) {
+      assert.include(error.toString(), `0x${code.toString(16)}`, `expected ${name}`);
+      return;
+    }
+    assert.fail(`Should have failed with ${name}`);
+  }
+
+  // Helper function: Create a user wallet in user-pda signed for by `authority`
+  async function createUserWallet(userId: string, authority: Keypair): Promise<PublicKey> {
+    const pda = derivePDA([Buffer.from("user"), Buffer.from(platform), Buffer.from(userId)], userPda.programId);
+
+    await userPda.methods
+      .createUserWallet(platform, userId)
+      .accounts({
+        userWallet: pda,
+        authority: authority.publicKey,
+        payer: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    return pda;
+  }
+
+  // Helper function: Create a user wallet in user-pda and register its $ME in
+  // me-token. The authority signs for the user and, unless `meAuthority` is
+  // given, pays the registration and so owns the ME wallet.
+  async function registerUser(
+    userId: string,
+    authority: Keypair = admin,
+    meAuthority: Keypair = authority
+  ): Promise<User> {
+    const qualifiedId = `${platform}:${userId}`;
+    const idHash = hashUserId(qualifiedId);
+    const pda = await createUserWallet(userId, authority);
+
+    const meAccount = derivePDA([Buffer.from("user_me"), idHash], meToken.programId);
+    const meMint = derivePDA([Buffer.from("me_mint"), idHash], meToken.programId);
+    const meWallet = derivePDA([Buffer.from("me_wallet"), idHash], meToken.programId);
+
+    await meToken.methods
+      .registerAndMintInitial(qualifiedId, Array.from(idHash))
+      .accounts({
+        userMeAccount: meAccount,
+        meMint,
+        userMeWallet: meWallet,
+        payer: meAuthority.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+      })
+      .signers(signersOf(meAuthority))
+      .rpc();
+
+    return { qualifiedId, idHash, pda, authority, meAccount, meMint, meWallet };
+  }
+
+  // Helper function: Propose a connection from User A to User B, escrowing 24 $ME
+  async function propose(
+    id: string,
+    userA: User,
+    userB: User,
+    noShowPolicy: object = policies.burn,
+    expiryPolicy: object = policies.refundUserA,
+    expirySeconds: number = 3600
+  ): Promise<Proposal> {
+    const idHash = hashConnectionId(id);
+    const connection = derivePDA([Buffer.from("connection"), idHash], program.programId);
+    const escrow = derivePDA([Buffer.from("escrow"), idHash], program.programId);
+    const proposal = {
+      id,
+      idHash,
+      connection,
+      escrow,
+      userA,
+      userB,
+      pinA: "1111",
+      saltA: randomBytes(32),
+      pinB: "2222",
+      saltB: randomBytes(32),
+    };
+
+    await program.methods
+      .initializeConnection(
+        id,
+        Array.from(idHash),
+        userA.qualifiedId,
+        userB.qualifiedId,
+        Array.from(userA.idHash),
+        Array.from(commitPin(proposal.saltA, connection, userA.pda, proposal.pinA)),
+        Array.from(commitPin(proposal.saltB, connection, userB.pda, proposal.pinB)),
+        new BN(expirySeconds),
+        noShowPolicy as any,
+        expiryPolicy as any
+      )
+      .accounts({
+        connection,
+        userAPda: userA.pda,
+        userBPda: userB.pda,
+        userAMeAccount: userA.meAccount,
+        userAMeWallet: userA.meWallet,
+        escrowMeWallet: escrow,
+        userAMeMint: userA.meMint,
+        userAAuthority: userA.authority.publicKey,
+        payer: rentPayer.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+      })
+      .signers([rentPayer, ...signersOf(userA.authority)])
+      .rpc();
+
+    return proposal;
+  }
+
+  // Helper function: Accept a proposed connection as User B
+  async function accept(proposal: Proposal) {
+    return program.methods
+      .acceptConnection(proposal.id, Array.from(proposal.idHash))
+      .accounts({
+        connection: proposal.connection,
+        userBPda: proposal.userB.pda,
+        userBAuthority: proposal.userB.authority.publicKey,
+      })
+      .signers(signersOf(proposal.userB.authority))
+      .rpc();
+  }
+
+  // Helper function: Submit the other user's PIN and salt as `user`
+  async function unlock(proposal: Proposal, user: User) {
+    const isUserA = user === proposal.userA;
+    const pin = isUserA ? proposal.pinB : proposal.pinA;
+    const salt = isUserA ? proposal.saltB : proposal.saltA;
+
+    return program.methods
+      .unlockWithPin(proposal.id, Array.from(proposal.idHash), pin, Array.from(salt))
+      .accounts({
+        connection: proposal.connection,
+        userPda: user.pda,
+        authority: user.authority.publicKey,
+        memoMint: derivePDA([Buffer.from("memo_mint")], program.programId),
+        userMemoWallet: derivePDA([Buffer.from("user_memo_wallet"), user.pda.toBuffer()], program.programId),
+        agentMemoWallet: derivePDA([Buffer.from("agent_memo_wallet"), admin.publicKey.toBuffer()], program.programId),
+        agent: admin.publicKey,
+        payer: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        rent: SYSVAR_RENT_PUBKEY,
+      })
+      .signers(signersOf(user.authority))
+      .rpc();
+  }
+
+  // Helper function: Settle a connection, optionally paying User B's wallet or
+  // refunding rent to another payer
+  async function settle(
+    proposal: Proposal,
+    userBMeWallet: PublicKey | null = null,
+    payer: PublicKey = rentPayer.publicKey
+  ) {
+    return program.methods
+      .settleConnection(proposal.id, Array.from(proposal.idHash))
+      .accounts({
+        connection: proposal.connection,
+        escrowMeWallet: proposal.escrow,
+        meMint: proposal.userA.meMint,
+        userAMeWallet: proposal.userA.meWallet,
+        userBPda: proposal.userB.pda,
+        userBMeWallet,
+        payer,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+  }
+
+  // Shared counterparties: Bob is custodial, Carol signs with her own key
+  let bob: User;
+  let carol: User;
+  const carolAuthority = Keypair.generate();
+
+  before("Start bankrun and register users", async () => {
+    context = await startAnchor(".", [], []);
+    provider = new BankrunProvider(context);
+    anchor.setProvider(provider);
+
+    program = new Program<HumanConnection>(require("../target/idl/human_connection.json"), provider);
+    meToken = new Program<MeToken>(require("../../me-token/target/idl/me_token.json"), provider);
+    userPda = new Program<UserPda>(require("../../user-pda/target/idl/user_pda.json"), provider);
+    admin = context.payer;
+
+    fund(rentPayer);
+    fund(carolAuthority);
+    bob = await registerUser("bob456");
+    carol = await registerUser("carol789", carolAuthority);
+
+    console.log("Program ID:", program.programId.toString());
+    console.log("User PDA program:", userPda.programId.toString());
+    console.log("ME Token program:", meToken.programId.toString());
+  });
+
+  describe("1. Settle Completed and Cancelled Connections", () => {
+    it("Should return the escrow to User A once both users unlock", async () => {
+      const alice = await registerUser("alice-completed");
+      const proposal = await propose("settle-completed", alice, bob);
+      assert.equal(await balanceOf(alice.meWallet), 24);
+      assert.equal(await balanceOf(proposal.escrow), 24);
+
+      await accept(proposal);
+      await unlock(proposal, alice);
+      await unlock(proposal, bob);
+
+      const connection = await program.account.connection.fetch(proposal.connection);
+      assert.deepEqual(connection.state, { completed: {} });
+
+      // Completed connections settle at once, without waiting for the deadline
+      const escrowRent = await lamportsOf(proposal.escrow);
+      const payerBefore = await lamportsOf(rentPayer.publicKey);
+      await settle(proposal);
+
+      assert.equal(await balanceOf(alice.meWallet), 48);
+      assert.isNull(await context.banksClient.getAccount(proposal.escrow));
+      assert.equal((await lamportsOf(rentPayer.publicKey)) - payerBefore, escrowRent);
+
+      console.log("✓ Completed connection refunded 24 $ME to User A and closed the escrow");
+    });
+
+    it("Should close the empty escrow of a declined connection", async () => {
+      const alice = await registerUser("alice-declined");
+      const proposal = await propose("settle-declined", alice, bob);
+
+      await program.methods
+        .declineConnection(proposal.id, Array.from(proposal.idHash))
+        .accounts({
+          connection: proposal.connection,
+          userBPda: bob.pda,
+          escrowMeWallet: proposal.escrow,
+          userAMeWallet: alice.meWallet,
+          userBAuthority: admin.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      assert.equal(await balanceOf(alice.meWallet), 48);
+      assert.equal(await balanceOf(proposal.escrow), 0);
+
+      const escrowRent = await lamportsOf(proposal.escrow);
+      const payerBefore = await lamportsOf(rentPayer.publicKey);
+      await settle(proposal);
+
+      const connection = await program.account.connection.fetch(proposal.connection);
+      assert.deepEqual(connection.state, { cancelled: {} });
+      assert.isNull(await context.banksClient.getAccount(proposal.escrow));
+      assert.equal((await lamportsOf(rentPayer.publicKey)) - payerBefore, escrowRent);
+
+      console.log("✓ Declined connection's escrow closed and rent refunded to the payer");
+    });
+
+    it("Should only refund rent to the connection's payer", async () => {
+      const alice = await registerUser("alice-payer");
+      const proposal = await propose("settle-wrong-payer", alice, bob);
+      await accept(proposal);
+      await unlock(proposal, alice);
+      await unlock(proposal, bob);
+
+      await expectError(settle(proposal, null, admin.publicKey), "ConstraintAddress");
+
+      console.log("✓ Rent refund to another account rejected");
+    });
+  });
+
+  describe("2. Settle Expired Connections", () => {
+    it("Should refuse to settle before the deadline", async () => {
+      const alice = await registerUser("alice-early");
+      const proposal = await propose("settle-early", alice, bob);
+      await accept(proposal);
+
+      await expectError(settle(proposal), "ConnectionNotExpired");
+
+      await warpPast(proposal.connection);
+      await settle(proposal);
+      assert.equal(await balanceOf(alice.meWallet), 48);
+
+      console.log("✓ Live connection could not be settled until its deadline passed");
+    });
+
+    it("Should refund User A when User B never accepted, whatever the expiry policy", async () => {
+      const alice = await registerUser("alice-unanswered");
+      const proposal = await propose("settle-unanswered", alice, bob, policies.burn, policies.burn);
+
+      await warpPast(proposal.connection);
+      await settle(proposal);
+
+      const connection = await program.account.connection.fetch(proposal.connection);
+      assert.deepEqual(connection.state, { expired: {} });
+      assert.equal(await balanceOf(alice.meWallet), 48);
+      assert.isNull(await context.banksClient.getAccount(proposal.escrow));
+
+      console.log("✓ Unanswered proposal refunded to User A");
+    });
+
+    it("Should refund User A when only User A unlocked", async () => {
+      const alice = await registerUser("alice-showed");
+      const proposal = await propose("settle-a-showed", alice, bob, policies.burn, policies.burn);
+      await accept(proposal);
+      await unlock(proposal, alice);
+
+      await warpPast(proposal.connection);
+      await settle(proposal);
+
+      assert.equal(await balanceOf(alice.meWallet), 48);
+
+      console.log("✓ User B's no-show refunded User A");
+    });
+
+    it("Should refuse to propose a no-show refund to User A", async () => {
+      const alice = await registerUser("alice-refund-no-show");
+
+      await expectError(
+        propose("refund-no-show", alice, bob, policies.refundUserA),
+        "InvalidNoShowPolicy"
+      );
+
+      console.log("✓ RefundUserA rejected as a no-show policy");
+    });
+
+    it("Should pay a no-show's escrow to User B's authority under PayUserB", async () => {
+      const alice = await registerUser("alice-no-show-pay");
+      const proposal = await propose("no-show-pay", alice, carol, policies.payUserB);
+      await accept(proposal);
+      await unlock(proposal, carol);
+
+      // Carol's authority holds the payout; her user PDA could never move it
+      const carolWallet = Keypair.generate().publicKey;
+      await setTokenAccount(carolWallet, alice.meMint, carolAuthority.publicKey, BigInt(0));
+
+      await warpPast(proposal.connection);
+      await expectError(settle(proposal), "MissingRecipient");
+
+      await nextSlot();
+      await settle(proposal, carolWallet);
+
+      const connection = await program.account.connection.fetch(proposal.connection);
+      assert.deepEqual(connection.state, { expired: {} });
+      assert.equal(await balanceOf(carolWallet), 24);
+      assert.equal(await balanceOf(alice.meWallet), 24);
+
+      console.log("✓ User A's no-show paid 24 $ME to User B");
+    });
+
+    it("Should burn a no-show's escrow under Burn", async () => {
+      const alice = await registerUser("alice-no-show-burn");
+      const proposal = await propose("no-show-burn", alice, bob, policies.burn);
+      await accept(proposal);
+      await unlock(proposal, bob);
+
+      await warpPast(proposal.connection);
+      const supplyBefore = await supplyOf(alice.meMint);
+      await settle(proposal);
+
+      assert.equal(supplyBefore - (await supplyOf(alice.meMint)), 24);
+      assert.equal(await balanceOf(alice.meWallet), 24);
+      assert.isNull(await context.banksClient.getAccount(proposal.escrow));
+
+      console.log("✓ User A's no-show burned 24 $ME");
+    });
+
+    for (const [name, policy] of Object.entries(policies)) {
+      it(`Should route an escrow nobody unlocked by the ${name} expiry policy`, async () => {
+        const alice = await registerUser(`alice-expired-${name}`);
+        const proposal = await propose(`expired-${name}`, alice, carol, policies.burn, policy);
+        await accept(proposal);
+
+        const carolWallet = Keypair.generate().publicKey;
+        await setTokenAccount(carolWallet, alice.meMint, carolAuthority.publicKey, BigInt(0));
+
+        await warpPast(proposal.connection);
+        const supplyBefore = await supplyOf(alice.meMint);
+        const escrowRent = await lamportsOf(proposal.escrow);
+        const payerBefore = await lamportsOf(rentPayer.publicKey);
+        await settle(proposal, carolWallet);
+
+        const settled = {
+          refundUserA: { userA: 48, userB: 0, burned: 0 },
+          payUserB: { userA: 24, userB: 24, burned: 0 },
+          burn: { userA: 24, userB: 0, burned: 24 },
+        }[name];
+        assert.equal(await balanceOf(alice.meWallet), settled.userA);
+        assert.equal(await balanceOf(carolWallet), settled.userB);
+        assert.equal(supplyBefore - (await supplyOf(alice.meMint)), settled.burned);
+        assert.equal((await lamportsOf(rentPayer.publicKey)) - payerBefore, escrowRent);
+
+        // The escrow is gone, so a second settle cannot even load it
+        await nextSlot();
+        await expectError(settle(proposal, carolWallet), "AccountNotInitialized");
+
+        console.log(`✓ Expired connection settled by ${name} and could not be settled again`);
+      });
+    }
+  });
+
+  describe("3. Settle Legacy Connections", () => {
+    // Connection as first deployed: no expiry, policies, payer or state
+    const LEGACY_CONNECTION_SPACE = 351;
+
+    // Helper function: Write a legacy-layout connection and its funded escrow
+    async function setLegacyConnection(id: string, userA: User, userB: User) {
+      const idHash = hashConnectionId(id);
+      const [connection, bump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("connection"), idHash],
+        program.programId
+      );
+      const escrow = derivePDA([Buffer.from("escrow"), idHash], program.programId);
+
+      const discriminator = program.idl.accounts.find((a) => a.name === "connection").discriminator;
+      const string = (value: string) => {
+        const length = Buffer.alloc(4);
+        length.writeUInt32LE(value.length);
+        return Buffer.concat([length, Buffer.from(value)]);
+      };
+      const createdAt = Buffer.alloc(8);
+      createdAt.writeBigInt64LE(BigInt(1_700_000_000));
+      const fields = Buffer.concat([
+        Buffer.from(discriminator),
+        string(id),
+        userA.pda.toBuffer(),
+        userB.pda.toBuffer(),
+        string(userA.qualifiedId),
+        string(userB.qualifiedId),
+        randomBytes(32),
+        randomBytes(32),
+        Buffer.from([0, 0]),
+        createdAt,
+        Buffer.from([bump]),
+      ]);
+      const data = Buffer.alloc(LEGACY_CONNECTION_SPACE);
+      fields.copy(data);
+
+      const rent = await context.banksClient.getRent();
+      context.setAccount(connection, {
+        lamports: Number(rent.minimumBalance(BigInt(LEGACY_CONNECTION_SPACE))),
+        data,
+        owner: program.programId,
+        executable: false,
+      });
+      await setTokenAccount(escrow, userA.meMint, connection, BigInt(24e9));
+
+      return { idHash, connection, escrow };
+    }
+
+    // Helper function: Settle a legacy connection, refunding rent to `rentRecipient`
+    async function settleLegacy(idHash: Buffer, connection: PublicKey, escrow: PublicKey, userA: User) {
+      return program.methods
+        .settleLegacyConnection(Array.from(idHash), Array.from(userA.idHash))
+        .accounts({
+          connection,
+          escrowMeWallet: escrow,
+          userAMeWallet: userA.meWallet,
+          rentRecipient: userA.authority.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+    }
+
+    it("Should refund a legacy escrow to User A and close both accounts", async () => {
+      // Dave's own key owns his ME wallet, so it receives the rent
+      const daveAuthority = Keypair.generate();
+      fund(daveAuthority);
+      const dave = await registerUser("dave-legacy", daveAuthority);
+      const { idHash, connection, escrow } = await setLegacyConnection("legacy-connection", dave, bob);
+
+      const rent = (await lamportsOf(connection)) + (await lamportsOf(escrow));
+      const ownerBefore = await lamportsOf(daveAuthority.publicKey);
+      await settleLegacy(idHash, connection, escrow, dave);
+
+      assert.equal(await balanceOf(dave.meWallet), 72);
+      assert.isNull(await context.banksClient.getAccount(escrow));
+      assert.isNull(await context.banksClient.getAccount(connection));
+      assert.equal((await lamportsOf(daveAuthority.publicKey)) - ownerBefore, rent);
+
+      console.log("✓ Legacy escrow refunded 24 $ME to User A's wallet and closed");
+    });
+
+    it("Should refuse a connection in the current layout", async () => {
+      const alice = await registerUser("alice-not-legacy");
+      const proposal = await propose("not-legacy", alice, bob);
+
+      await expectError(
+        settleLegacy(proposal.idHash, proposal.connection, proposal.escrow, alice),
+        "NotLegacyConnection"
+      );
+
+      console.log("✓ Current-layout connection rejected by settle_legacy_connection");
+    });
+  });
+
+  describe("4. User Wallet Checks", () => {
+    let alice: User;
+    let proposal: Proposal;
+
+    // Helper function: Copy a user wallet's data to `address`, owned by `owner`
+    async function forgeUserWallet(user: User, address: PublicKey, owner: PublicKey): Promise<User> {
+      const account = await context.banksClient.getAccount(user.pda);
+      context.setAccount(address, { ...account, owner });
+      return { ...user, pda: address };
+    }
+
+    // Helper function: The same user wallet forged under another program's PDA
+    async function forgeFromOtherProgram(user: User): Promise<User> {
+      const userId = user.qualifiedId.split(":")[1];
+      const address = derivePDA([Buffer.from("user"), Buffer.from(platform), Buffer.from(userId)], program.programId);
+      return forgeUserWallet(user, address, program.programId);
+    }
+
+    // Helper function: The same user wallet forged at an address off its seeds
+    async function forgeAtWrongSeeds(user: User): Promise<User> {
+      return forgeUserWallet(user, Keypair.generate().publicKey, userPda.programId);
+    }
+
+    before("Propose and accept a connection", async () => {
+      alice = await registerUser("alice-wallets");
+      proposal = await propose("wallet-checks", alice, bob);
+      await accept(proposal);
+    });
+
+    it("Should reject a user wallet owned by another program", async () => {
+      const fakeAlice = await forgeFromOtherProgram(alice);
+      await expectError(propose("wrong-program", fakeAlice, bob), "AccountOwnedByWrongProgram");
+
+      const fakeBob = await forgeFromOtherProgram(bob);
+      await expectError(unlock(proposal, fakeBob), "AccountOwnedByWrongProgram");
+
+      console.log("✓ User wallets from another program rejected");
+    });
+
+    it("Should reject a user wallet at the wrong seeds", async () => {
+      const fakeAlice = await forgeAtWrongSeeds(alice);
+      await expectError(propose("wrong-seeds", fakeAlice, bob), "ConstraintSeeds");
+
+      const fakeBob = await forgeAtWrongSeeds(bob);
+      await expectError(unlock(proposal, fakeBob), "ConstraintSeeds");
+
+      console.log("✓ User wallets off their [\"user\", platform, user_id] seeds rejected");
+    });
+
+    it("Should reject a signer that is not the user wallet's authority", async () => {
+      await expectError(
+        propose("wrong-authority", { ...alice, authority: carolAuthority }, bob),
+        "UnauthorizedAuthority"
+      );
+      // Alice has no MEMO wallet yet, so none is checked against the wrong owner first
+      await expectError(
+        unlock(proposal, { ...alice, authority: carolAuthority }),
+        "UnauthorizedAuthority"
+      );
+
+      console.log("✓ Signers other than the wallet authority rejected");
+    });
+
+    it("Should mint the MEMO reward to a wallet the user's authority owns", async () => {
+      const carolProposal = await propose("memo-owner", alice, carol);
+      await accept(carolProposal);
+      await unlock(carolProposal, carol);
+
+      const memoWallet = derivePDA([Buffer.from("user_memo_wallet"), carol.pda.toBuffer()], program.programId);
+      const account = await context.banksClient.getAccount(memoWallet);
+      assert.equal(AccountLayout.decode(account.data).owner.toString(), carolAuthority.publicKey.toString());
+      assert.equal(await balanceOf(memoWallet), 8);
+
+      console.log("✓ 8 $MEMO minted to a wallet owned by User B's authority");
+    });
+  });
+
+  describe("5. User A's ME Mint and Wallet", () => {
+    it("Should reject a mint other than User A's registered ME mint", async () => {
+      const alice = await registerUser("alice-mint");
+
+      await expectError(
+        propose("wrong-mint", { ...alice, meMint: bob.meMint }, bob),
+        "ConstraintTokenMint"
+      );
+
+      console.log("✓ Another user's ME mint rejected");
+    });
+
+    it("Should reject a user whose ME wallet is owned by someone else", async () => {
+      // Erin's user wallet is signed for by her own key, but admin paid her
+      // me_token registration and so owns her ME wallet
+      const erinAuthority = Keypair.generate();
+      fund(erinAuthority);
+      const erin = await registerUser("erin-owner", erinAuthority, admin);
+
+      await expectError(propose("wrong-owner", erin, bob), "UnauthorizedAuthority");
+      await expectError(
+        propose("wrong-owner", { ...erin, authority: admin }, bob),
+        "UnauthorizedAuthority"
+      );
+
+      console.log("✓ ME wallet owned by another key than the user's authority rejected");
+    });
+
+    it("Should accept a user_me account keyed by the legacy unprefixed hash", async () => {
+      // Migrated accounts keep the plain sha256 key, which me-token no longer
+      // registers, so write one directly
+      const frankAuthority = Keypair.generate();
+      fund(frankAuthority);
+      const userId = "frank-legacy";
+      const qualifiedId = `${platform}:${userId}`;
+      const idHash = createHash('sha256').update(qualifiedId).digest();
+      const pda = await createUserWallet(userId, frankAuthority);
+
+      const [meAccount, meAccountBump] = PublicKey.findProgramAddressSync(
+        [Buffer.from("user_me"), idHash],
+        meToken.programId
+      );
+      const meMint = derivePDA([Buffer.from("me_mint"), idHash], meToken.programId);
+      const meWallet = derivePDA([Buffer.from("me_wallet"), idHash], meToken.programId);
+      const rent = await context.banksClient.getRent();
+
+      const userIdBytes = Buffer.alloc(64);
+      Buffer.from(qualifiedId).copy(userIdBytes);
+      const meAccountData = await program.coder.accounts.encode("userMeAccount", {
+        userId: Array.from(userIdBytes),
+        meMint,
+        lastClaimTime: new BN(0),
+        totalMinted: new BN(48),
+        authority: frankAuthority.publicKey,
+        delegate: PublicKey.default,
+        bump: meAccountBump,
+      });
+      context.setAccount(meAccount, {
+        lamports: Number(rent.minimumBalance(BigInt(meAccountData.length))),
+        data: meAccountData,
+        owner: meToken.programId,
+        executable: false,
+      });
+
+      const mintData = Buffer.alloc(MINT_SIZE);
+      MintLayout.encode(
+        {
+          mintAuthorityOption: 1,
+          mintAuthority: meMint,
+          supply: BigInt(48e9),
+          decimals: 9,
+          isInitialized: true,
+          freezeAuthorityOption: 0,
+          freezeAuthority: PublicKey.default,
+        },
+        mintData
+      );
+      context.setAccount(meMint, {
+        lamports: Number(rent.minimumBalance(BigInt(MINT_SIZE))),
+        data: mintData,
+        owner: TOKEN_PROGRAM_ID,
+        executable: false,
+      });
+      await setTokenAccount(meWallet, meMint, frankAuthority.publicKey, BigInt(48e9));
+
+      const frank = { qualifiedId, idHash, pda, authority: frankAuthority, meAccount, meMint, meWallet };
+      const proposal = await propose("legacy-hash", frank, bob);
+
+      assert.equal(await balanceOf(proposal.escrow), 24);
+      assert.equal(await balanceOf(meWallet), 24);
+
+      console.log("✓ Connection proposed from a user_me account under the legacy hash");
+    });
+  });
+});
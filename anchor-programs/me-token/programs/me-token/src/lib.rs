@@ -1,23 +1,103 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 use anchor_spl::token::{self, Token, TokenAccount, Mint, MintTo};
+use sha2::{Sha256, Digest};
 
 declare_id!("CbTbi8L4kmQeHNsCVJUVRH4PCWFYBvHq7vQqPaVd3SB3");
 
+const USER_ID_DOMAIN: &[u8] = b"withparen:user_id:v1:";
+
+const DAILY_LIMIT: u64 = 24;
+const DAY_IN_SECONDS: i64 = 86400;
+// Days of unclaimed $ME that keep accruing, until the admin changes it
+const DEFAULT_CARRY_OVER_DAYS: u64 = 3;
+const MAX_CARRY_OVER_DAYS: u64 = 30;
+
+/// Hash a platform-qualified user ID (e.g. "telegram:user123") under the
+/// domain prefix shared with the unified program. All user PDAs derive from it.
+pub fn hash_user_id(user_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(USER_ID_DOMAIN);
+    hasher.update(user_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Hash of a user ID without the domain prefix. user_me accounts registered
+/// before the prefix existed are keyed by it, and migration keeps that key.
+pub fn legacy_hash_user_id(user_id: &str) -> [u8; 32] {
+    Sha256::digest(user_id.as_bytes()).into()
+}
+
+/// Whether `hash` keys the user_me account of `user_id`, under either the
+/// current or the legacy hash.
+pub fn is_user_id_hash(user_id: &str, hash: &[u8; 32]) -> bool {
+    hash_user_id(user_id) == *hash || legacy_hash_user_id(user_id) == *hash
+}
+
+/// Whole $ME accrued over `elapsed` seconds at DAILY_LIMIT per day, capped at
+/// `carry_over_days` days, and the seconds of accrual it pays for. Below the
+/// cap that is rounded up to never pay ahead; at the cap it is all of
+/// `elapsed`, forfeiting the excess.
+pub fn accrued_me(elapsed: i64, carry_over_days: u64) -> (u64, i64) {
+    let elapsed = elapsed.max(0);
+    let limit = DAILY_LIMIT as u128;
+    let day = DAY_IN_SECONDS as u128;
+    let cap = DAILY_LIMIT * carry_over_days;
+
+    let accrued = (elapsed as u128 * limit / day) as u64;
+    if accrued >= cap {
+        return (cap, elapsed);
+    }
+    let paid_seconds = (accrued as u128 * day).div_ceil(limit) as i64;
+    (accrued, paid_seconds)
+}
+
 #[program]
 pub mod me_token {
     use super::*;
 
     const INITIAL_MINT: u64 = 48;
-    const DAILY_LIMIT: u64 = 24;
-    const DAY_IN_SECONDS: i64 = 86400;
     const ME_DECIMALS: u8 = 9;
 
-    /// Register a PDA and create their personal $ME token with initial mint
+    /// Create the program config with the default carry-over cap. Only the
+    /// program's upgrade authority may call it, so nobody can front-run the
+    /// deployer to the config PDA; the signer becomes the config admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.carry_over_days = DEFAULT_CARRY_OVER_DAYS;
+        config.bump = ctx.bumps.config;
+
+        msg!("Config initialized, admin {}", config.admin);
+        Ok(())
+    }
+
+    /// Set how many days of unclaimed $ME keep accruing (1 to 30). Admin only.
+    pub fn set_carry_over_days(ctx: Context<UpdateConfig>, carry_over_days: u64) -> Result<()> {
+        require!(
+            (1..=MAX_CARRY_OVER_DAYS).contains(&carry_over_days),
+            ErrorCode::InvalidCarryOverDays
+        );
+        ctx.accounts.config.carry_over_days = carry_over_days;
+
+        msg!("Carry-over cap set to {} days", carry_over_days);
+        Ok(())
+    }
+
+    /// Register a PDA and create their personal $ME token with initial mint.
+    /// The payer owns the ME wallet and becomes the user's authority.
     pub fn register_and_mint_initial(
         ctx: Context<RegisterUser>,
         user_id: String,
         user_id_hash: [u8; 32],
     ) -> Result<()> {
+        require!(user_id.len() <= 64, ErrorCode::UserIdTooLong);
+        require!(!user_id.is_empty(), ErrorCode::InvalidUserId);
+        require!(
+            hash_user_id(&user_id) == user_id_hash,
+            ErrorCode::InvalidUserIdHash
+        );
+
         let user_me_account = &mut ctx.accounts.user_me_account;
         let clock = Clock::get()?;
 
@@ -28,9 +108,10 @@ pub mod me_token {
         user_id_array[..len].copy_from_slice(&user_id_bytes[..len]);
         user_me_account.user_id = user_id_array;
         user_me_account.me_mint = ctx.accounts.me_mint.key();
-        user_me_account.last_mint_time = clock.unix_timestamp;
-        user_me_account.daily_minted_today = INITIAL_MINT;
+        user_me_account.last_claim_time = clock.unix_timestamp;
         user_me_account.total_minted = INITIAL_MINT;
+        user_me_account.authority = ctx.accounts.payer.key();
+        user_me_account.delegate = Pubkey::default();
         user_me_account.bump = ctx.bumps.user_me_account;
 
         // Mint initial 48 $ME tokens
@@ -58,34 +139,94 @@ pub mod me_token {
         Ok(())
     }
 
-    /// Mint daily $ME tokens (max 24/day with 24-hour reset)
-    pub fn mint_daily(
-        ctx: Context<MintDaily>,
+    /// Set or clear (with the default pubkey) the delegate allowed to call
+    /// claim_me on the user's behalf, such as an agent's hot key. Owner only.
+    pub fn set_delegate(
+        ctx: Context<SetDelegate>,
+        _user_id_hash: [u8; 32],
+        delegate: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.user_me_account.delegate = delegate;
+
+        msg!("Mint delegate set to {}", delegate);
+        Ok(())
+    }
+
+    /// Upgrade a user_me account registered before authority and delegate
+    /// existed. The owner of the user's ME wallet becomes the authority and
+    /// signs; accrual resumes from the legacy last mint time.
+    pub fn migrate_user_me_account(
+        ctx: Context<MigrateUserMe>,
+        _user_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let info = ctx.accounts.user_me_account.to_account_info();
+        require!(
+            info.data_len() == LegacyUserMeAccount::SPACE,
+            ErrorCode::AlreadyMigrated
+        );
+
+        let legacy = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data[..8] == *UserMeAccount::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            LegacyUserMeAccount::deserialize(&mut &data[8..])?
+        };
+
+        // Top up rent for the larger layout, then grow the account
+        let shortfall = Rent::get()?
+            .minimum_balance(UserMeAccount::SPACE)
+            .saturating_sub(info.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        info.resize(UserMeAccount::SPACE)?;
+
+        let migrated = UserMeAccount {
+            user_id: legacy.user_id,
+            me_mint: legacy.me_mint,
+            last_claim_time: legacy.last_mint_time,
+            total_minted: legacy.total_minted,
+            authority: ctx.accounts.authority.key(),
+            delegate: Pubkey::default(),
+            bump: legacy.bump,
+        };
+        let mut data = info.try_borrow_mut_data()?;
+        migrated.try_serialize(&mut &mut data[..])?;
+
+        msg!("Migrated user_me account, authority {}", migrated.authority);
+        Ok(())
+    }
+
+    /// Mint the $ME accrued since the last claim. $ME accrues continuously at
+    /// 24/day, up to config.carry_over_days worth; time beyond that is forfeited.
+    /// Must be signed by the user's authority or its delegate.
+    pub fn claim_me(
+        ctx: Context<ClaimMe>,
         user_id: String,
         user_id_hash: [u8; 32],
     ) -> Result<()> {
         let user_me_account = &mut ctx.accounts.user_me_account;
         let clock = Clock::get()?;
 
-        // Check if a new day has passed
-        let time_elapsed = clock.unix_timestamp - user_me_account.last_mint_time;
-        let days_passed = time_elapsed / DAY_IN_SECONDS;
-
-        if days_passed > 0 {
-            // Reset daily counter for new day
-            user_me_account.daily_minted_today = 0;
-            user_me_account.last_mint_time = clock.unix_timestamp;
-        }
-
-        // Check daily limit
-        require!(
-            user_me_account.daily_minted_today < DAILY_LIMIT,
-            ErrorCode::DailyLimitReached
+        let (to_mint, paid_seconds) = accrued_me(
+            clock.unix_timestamp - user_me_account.last_claim_time,
+            ctx.accounts.config.carry_over_days,
         );
+        require!(to_mint > 0, ErrorCode::NothingToClaim);
 
-        // Calculate how many tokens can be minted
-        let available_to_mint = DAILY_LIMIT - user_me_account.daily_minted_today;
-        let to_mint = available_to_mint.min(DAILY_LIMIT);
+        // Only advance by the time that was paid out, so a partial token keeps accruing
+        user_me_account.last_claim_time += paid_seconds;
 
         // Mint $ME tokens
         let seeds = &[
@@ -108,22 +249,61 @@ pub mod me_token {
             to_mint * 10u64.pow(ME_DECIMALS as u32),
         )?;
 
-        user_me_account.daily_minted_today += to_mint;
         user_me_account.total_minted += to_mint;
 
-        msg!("Minted {} $ME for {} (total lifetime: {})",
+        msg!("Claimed {} $ME for {} (total lifetime: {})",
              to_mint, user_id, user_me_account.total_minted);
         Ok(())
     }
 }
 
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = MeConfig::SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, MeConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub program: Program<'info, crate::program::MeToken>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin,
+    )]
+    pub config: Account<'info, MeConfig>,
+
+    pub admin: Signer<'info>,
+}
+
 #[derive(Accounts)]
 #[instruction(user_id: String, user_id_hash: [u8; 32])]
 pub struct RegisterUser<'info> {
     #[account(
         init,
         payer = payer,
-        space = 8 + 64 + 32 + 8 + 8 + 8 + 1,
+        space = UserMeAccount::SPACE,
         seeds = [b"user_me", user_id_hash.as_ref()],
         bump
     )]
@@ -159,7 +339,7 @@ pub struct RegisterUser<'info> {
 
 #[derive(Accounts)]
 #[instruction(user_id: String, user_id_hash: [u8; 32])]
-pub struct MintDaily<'info> {
+pub struct ClaimMe<'info> {
     #[account(
         mut,
         seeds = [b"user_me", user_id_hash.as_ref()],
@@ -181,24 +361,174 @@ pub struct MintDaily<'info> {
     )]
     pub user_me_wallet: Account<'info, TokenAccount>,
 
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, MeConfig>,
+
+    /// The user's authority or delegate
+    #[account(
+        constraint = authority.key() == user_me_account.authority
+            || (user_me_account.delegate != Pubkey::default() && authority.key() == user_me_account.delegate)
+            @ ErrorCode::UnauthorizedMinter
+    )]
+    pub authority: Signer<'info>,
+
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+#[instruction(user_id_hash: [u8; 32])]
+pub struct SetDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_me", user_id_hash.as_ref()],
+        bump = user_me_account.bump,
+        has_one = authority @ ErrorCode::UnauthorizedMinter,
+    )]
+    pub user_me_account: Account<'info, UserMeAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_id_hash: [u8; 32])]
+pub struct MigrateUserMe<'info> {
+    /// CHECK: Legacy layout; the handler checks size and discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"user_me", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_me_account: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"me_wallet", user_id_hash.as_ref()],
+        bump,
+        constraint = user_me_wallet.owner == authority.key() @ ErrorCode::UnauthorizedMinter
+    )]
+    pub user_me_wallet: Account<'info, TokenAccount>,
+
+    /// Owner of the ME wallet, becomes the account authority
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+pub struct MeConfig {
+    pub admin: Pubkey,             // May change the config (32 bytes)
+    pub carry_over_days: u64,      // Days of unclaimed $ME that accrue (8 bytes)
+    pub bump: u8,                  // PDA bump seed (1 byte)
+}
+
+impl MeConfig {
+    pub const SPACE: usize = 8 + 32 + 8 + 1;
+}
+
 #[account]
 pub struct UserMeAccount {
     pub user_id: [u8; 64],         // User identifier (fixed 64 bytes)
     pub me_mint: Pubkey,           // Personal ME token mint address (32 bytes)
-    pub last_mint_time: i64,       // Unix timestamp of last mint (8 bytes)
-    pub daily_minted_today: u64,   // Amount minted today (8 bytes)
+    pub last_claim_time: i64,      // $ME has been paid out up to here (8 bytes)
     pub total_minted: u64,         // Total lifetime minted (8 bytes)
+    pub authority: Pubkey,         // Owner, may mint and set the delegate (32 bytes)
+    pub delegate: Pubkey,          // Optional extra minter, default if none (32 bytes)
     pub bump: u8,                  // PDA bump seed (1 byte)
 }
 
+impl UserMeAccount {
+    pub const SPACE: usize = 8 + 64 + 32 + 8 + 8 + 32 + 32 + 1;
+}
+
+/// UserMeAccount as first deployed, before authority and delegate.
+/// Shares the UserMeAccount discriminator; only read by the migration.
+#[derive(AnchorDeserialize)]
+pub struct LegacyUserMeAccount {
+    pub user_id: [u8; 64],
+    pub me_mint: Pubkey,
+    pub last_mint_time: i64,
+    pub daily_minted_today: u64,
+    pub total_minted: u64,
+    pub bump: u8,
+}
+
+impl LegacyUserMeAccount {
+    pub const SPACE: usize = 8 + 64 + 32 + 8 + 8 + 8 + 1;
+}
+
 #[error_code]
 pub enum ErrorCode {
+    // Unused since claim_me replaced daily minting, kept for error-code stability
     #[msg("Daily minting limit of 24 $ME reached. Try again tomorrow.")]
     DailyLimitReached,
 
     #[msg("User ID too long (max 64 bytes)")]
     UserIdTooLong,
+
+    #[msg("User ID must not be empty")]
+    InvalidUserId,
+
+    #[msg("User ID hash does not match the user ID")]
+    InvalidUserIdHash,
+
+    #[msg("Signer is not the user's authority or delegate")]
+    UnauthorizedMinter,
+
+    #[msg("No $ME has accrued since the last claim")]
+    NothingToClaim,
+
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+
+    #[msg("Carry-over cap must be between 1 and 30 days")]
+    InvalidCarryOverDays,
+
+    #[msg("Signer is not the config admin")]
+    UnauthorizedAdmin,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_id_hash_accepts_current_and_legacy_keys() {
+        let user_id = "telegram:alice123";
+        assert!(is_user_id_hash(user_id, &hash_user_id(user_id)));
+        assert!(is_user_id_hash(user_id, &legacy_hash_user_id(user_id)));
+        assert_ne!(hash_user_id(user_id), legacy_hash_user_id(user_id));
+
+        assert!(!is_user_id_hash(user_id, &hash_user_id("telegram:bob456")));
+        assert!(!is_user_id_hash(user_id, &legacy_hash_user_id("telegram:bob456")));
+    }
+
+    #[test]
+    fn accrued_me_below_one_token_pays_nothing() {
+        assert_eq!(accrued_me(-10, DEFAULT_CARRY_OVER_DAYS), (0, 0));
+        assert_eq!(accrued_me(0, DEFAULT_CARRY_OVER_DAYS), (0, 0));
+        assert_eq!(accrued_me(3599, DEFAULT_CARRY_OVER_DAYS), (0, 0));
+    }
+
+    #[test]
+    fn accrued_me_pays_whole_tokens_for_partial_days() {
+        assert_eq!(accrued_me(3601, DEFAULT_CARRY_OVER_DAYS), (1, 3600));
+        assert_eq!(accrued_me(86400 / 2 + 100, DEFAULT_CARRY_OVER_DAYS), (12, 86400 / 2));
+        assert_eq!(accrued_me(86400 + 7200, DEFAULT_CARRY_OVER_DAYS), (26, 86400 + 7200));
+    }
+
+    #[test]
+    fn accrued_me_caps_at_carry_over_and_forfeits_the_rest() {
+        assert_eq!(accrued_me(3 * 86400, 3), (72, 3 * 86400));
+        assert_eq!(accrued_me(10 * 86400, 3), (72, 10 * 86400));
+
+        // A larger cap keeps accruing further
+        assert_eq!(accrued_me(10 * 86400, 7), (168, 10 * 86400));
+        assert_eq!(accrued_me(5 * 86400, 1), (24, 5 * 86400));
+    }
 }
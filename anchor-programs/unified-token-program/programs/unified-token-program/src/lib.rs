@@ -1,17 +1,82 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
-    token::{self, Mint, MintTo, Token, TokenAccount, Transfer},
+    token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer},
 };
 use sha2::{Digest, Sha256};
 
 declare_id!("GXnod1W71vzjuFkXHxwQ2dkBe7t1auJMtwMQYL67ytVt");
 
 // Constants
-const INITIAL_ME_MINT: u64 = 48;
-const DAILY_ME_LIMIT: u64 = 24;
-const DAY_IN_SECONDS: i64 = 86400;
 const TOKEN_DECIMALS: u8 = 9;
-const CONNECTION_MEMO_REWARD: u64 = 8;
+const MIN_CONNECTION_EXPIRY: i64 = 3600;
+const MAX_CONNECTION_EXPIRY: i64 = 30 * DEFAULT_DAY_IN_SECONDS;
+const MIN_CONNECTION_STAKE: u64 = 1;
+const MAX_CONNECTION_STAKE: u64 = 10 * DEFAULT_DAILY_ME_LIMIT;
+const BPS_DENOMINATOR: u64 = 10_000;
+const PIN_COMMITMENT_DOMAIN: &[u8] = b"withparen:pin:v1";
+const USER_ID_DOMAIN: &[u8] = b"withparen:user_id:v1:";
+
+// Default tokenomics, copied into GlobalState.config at initialization and
+// adjustable afterwards through update_config
+const DEFAULT_INITIAL_ME_MINT: u64 = 48;
+const DEFAULT_DAILY_ME_LIMIT: u64 = 24;
+const DEFAULT_DAY_IN_SECONDS: i64 = 86400;
+// Days of unclaimed ME that keep accruing before the rest is forfeited
+const DEFAULT_ME_CARRY_OVER_DAYS: u64 = 3;
+const DEFAULT_CONNECTION_MEMO_REWARD: u64 = 8;
+const DEFAULT_AGENT_MEMO_REWARD: u64 = 8;
+// No bond required until the admin sets one, since agents start without MEMO
+const DEFAULT_MIN_AGENT_BOND: u64 = 0;
+const DEFAULT_AGENT_UNBOND_COOLDOWN: i64 = 7 * 86400;
+// Repeat meetings of the same two users: minimum gap between connections,
+// and the share of the previous reward paid for each repeat
+const DEFAULT_PAIR_COOLDOWN: i64 = 86400;
+const DEFAULT_PAIR_REWARD_DECAY_BPS: u64 = 5_000;
+// Share of the cancelling user's stake burned when an accepted connection is cancelled
+const DEFAULT_CANCEL_PENALTY_BPS: u64 = 1_000;
+// Share of a no-show's stake paid to the user who showed up; the rest is burned
+const DEFAULT_NO_SHOW_FORFEIT_BPS: u64 = 5_000;
+// Share of the ME burned when a lock position is withdrawn before maturity
+const DEFAULT_EARLY_WITHDRAWAL_PENALTY_BPS: u64 = 1_000;
+// Wrong PINs a side may submit before it is locked out, and for how long
+const DEFAULT_MAX_PIN_ATTEMPTS: u8 = 5;
+const DEFAULT_PIN_LOCKOUT_SECONDS: i64 = 3600;
+const LOCK_TIER_COUNT: usize = 3;
+// (lock duration in days, MEMO multiplier in bps)
+const DEFAULT_LOCK_TIERS: [(u16, u64); LOCK_TIER_COUNT] = [(7, 10_000), (30, 15_000), (90, 20_000)];
+
+// Bounds enforced by update_config
+const MAX_INITIAL_ME_MINT: u64 = 1_000;
+const MAX_DAILY_ME_LIMIT: u64 = 1_000;
+const MAX_CONNECTION_MEMO_REWARD: u64 = 1_000;
+const MIN_DAY_IN_SECONDS: i64 = 3600;
+const MAX_DAY_IN_SECONDS: i64 = 7 * 86400;
+const MAX_ME_CARRY_OVER_DAYS: u64 = 30;
+const MAX_LOCK_DURATION_DAYS: u16 = 365;
+const MAX_LOCK_MULTIPLIER_BPS: u64 = 50_000;
+const MAX_MIN_AGENT_BOND: u64 = 1_000_000;
+const MIN_AGENT_UNBOND_COOLDOWN: i64 = 3600;
+const MAX_AGENT_UNBOND_COOLDOWN: i64 = 90 * 86400;
+const MAX_PAIR_COOLDOWN: i64 = 30 * 86400;
+const MAX_PIN_ATTEMPTS: u8 = 20;
+const MIN_PIN_LOCKOUT_SECONDS: i64 = 60;
+const MAX_PIN_LOCKOUT_SECONDS: i64 = 7 * 86400;
+
+const MAX_ADMIN_SIGNERS: usize = 5;
+
+// Pause bits in GlobalState.pause_flags, one per mint or transfer path
+const PAUSE_INITIALIZE_USER: u32 = 1 << 0;
+const PAUSE_CLAIM_ME: u32 = 1 << 1;
+const PAUSE_LOCK_ME_FOR_MEMO: u32 = 1 << 2;
+const PAUSE_WITHDRAW_LOCKED_ME: u32 = 1 << 3;
+const PAUSE_CREATE_CONNECTION: u32 = 1 << 4;
+const PAUSE_UNLOCK_CONNECTION: u32 = 1 << 5;
+const PAUSE_EXPIRE_CONNECTION: u32 = 1 << 6;
+const PAUSE_SETTLE_NO_SHOW: u32 = 1 << 7;
+const PAUSE_AGENT_BOND: u32 = 1 << 8;
+const PAUSE_RESPOND_TO_CONNECTION: u32 = 1 << 9;
+const PAUSE_CANCEL_CONNECTION: u32 = 1 << 10;
+const PAUSE_RESCHEDULE_CONNECTION: u32 = 1 << 11;
 
 #[program]
 pub mod unified_token_program {
@@ -21,14 +86,369 @@ pub mod unified_token_program {
     pub fn initialize_global(ctx: Context<InitializeGlobal>) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
         global_state.memo_mint = ctx.accounts.memo_mint.key();
-        global_state.me_escrow = ctx.accounts.me_escrow.key();
         global_state.admin = ctx.accounts.admin.key();
+        global_state.pending_admin = Pubkey::default();
+        global_state.admin_multisig = AdminMultisig::default();
+        global_state.guardian = Pubkey::default();
+        global_state.pause_flags = 0;
         global_state.total_users = 0;
         global_state.total_connections = 0;
+        global_state.config = ProgramConfig::default();
 
-        msg!("Global state initialized");
-        msg!("MEMO Mint: {}", global_state.memo_mint);
-        msg!("ME Escrow: {}", global_state.me_escrow);
+        emit!(GlobalInitialized {
+            admin: global_state.admin,
+            memo_mint: global_state.memo_mint,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Replace the tokenomics parameters. Admin only; every field is bounds
+    /// checked and the previous and new values are emitted as an event.
+    pub fn update_config(ctx: Context<UpdateConfig>, config: ProgramConfig) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+        config.validate()?;
+
+        let global_state = &mut ctx.accounts.global_state;
+        let old_config = global_state.config;
+        global_state.config = config;
+
+        emit!(ConfigUpdated {
+            admin: ctx.accounts.admin.key(),
+            old_config,
+            new_config: config,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// First step of an admin transfer: record the proposed admin. Proposing
+    /// Pubkey::default() cancels a pending transfer.
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.pending_admin = new_admin;
+
+        emit!(AdminProposed {
+            admin: global_state.admin,
+            pending_admin: new_admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Second step of an admin transfer: the proposed admin signs to take over
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let previous_admin = global_state.admin;
+        global_state.admin = ctx.accounts.new_admin.key();
+        global_state.pending_admin = Pubkey::default();
+
+        emit!(AdminTransferred {
+            previous_admin,
+            new_admin: global_state.admin,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the M-of-N admin multisig. While a multisig is set, admin
+    /// instructions need `threshold` of its members to sign (the `admin`
+    /// account plus co-signers passed as remaining accounts) and the single
+    /// admin key is ignored. An empty signer list with threshold 0 removes it.
+    pub fn set_admin_multisig(
+        ctx: Context<SetAdminMultisig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+
+        require!(signers.len() <= MAX_ADMIN_SIGNERS, ErrorCode::InvalidMultisig);
+        require!(threshold as usize <= signers.len(), ErrorCode::InvalidMultisig);
+        require!(signers.is_empty() || threshold > 0, ErrorCode::InvalidMultisig);
+        for (i, signer) in signers.iter().enumerate() {
+            require!(*signer != Pubkey::default(), ErrorCode::InvalidMultisig);
+            require!(!signers[..i].contains(signer), ErrorCode::InvalidMultisig);
+        }
+
+        let mut multisig = AdminMultisig {
+            threshold,
+            signer_count: signers.len() as u8,
+            ..AdminMultisig::default()
+        };
+        multisig.signers[..signers.len()].copy_from_slice(&signers);
+        ctx.accounts.global_state.admin_multisig = multisig;
+
+        emit!(AdminMultisigUpdated {
+            signers,
+            threshold,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Register a matchmaker agent allowed to create connections, or
+    /// re-activate a revoked one. Admin only; stats survive re-registration.
+    pub fn register_agent(ctx: Context<RegisterAgent>, agent: Pubkey) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+
+        let clock = Clock::get()?;
+        let agent_account = &mut ctx.accounts.agent_account;
+        agent_account.agent = agent;
+        agent_account.active = true;
+        agent_account.registered_at = clock.unix_timestamp;
+        agent_account.bump = ctx.bumps.agent_account;
+
+        emit!(AgentRegistered {
+            agent,
+            admin: ctx.accounts.admin.key(),
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke an agent so it can no longer create connections. Admin only.
+    pub fn revoke_agent(ctx: Context<RevokeAgent>) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+
+        let agent_account = &mut ctx.accounts.agent_account;
+        agent_account.active = false;
+
+        emit!(AgentRevoked {
+            agent: agent_account.agent,
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Add MEMO to the calling agent's bond. create_connection requires the
+    /// bond to cover config.min_agent_bond.
+    pub fn bond_agent(ctx: Context<BondAgent>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.agent_memo_ata.to_account_info(),
+                    to: ctx.accounts.agent_bond_vault.to_account_info(),
+                    authority: ctx.accounts.agent.to_account_info(),
+                },
+            ),
+            amount * 10u64.pow(TOKEN_DECIMALS as u32),
+        )?;
+
+        let agent_account = &mut ctx.accounts.agent_account;
+        agent_account.bonded_amount += amount;
+
+        emit!(AgentBonded {
+            agent: agent_account.agent,
+            amount,
+            bonded_amount: agent_account.bonded_amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Start unbonding part of the agent's bond. The MEMO stays in the vault,
+    /// and slashable, until config.agent_unbond_cooldown has passed. A new
+    /// request adds to the pending amount and restarts the cooldown.
+    pub fn request_unbond(ctx: Context<RequestUnbond>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let cooldown = ctx.accounts.global_state.config.agent_unbond_cooldown;
+        let clock = Clock::get()?;
+        let agent_account = &mut ctx.accounts.agent_account;
+        require!(amount <= agent_account.bonded_amount, ErrorCode::InsufficientAgentBond);
+
+        agent_account.bonded_amount -= amount;
+        agent_account.unbonding_amount += amount;
+        agent_account.unbonding_at = clock.unix_timestamp + cooldown;
+
+        emit!(AgentUnbondRequested {
+            agent: agent_account.agent,
+            amount,
+            unbonding_amount: agent_account.unbonding_amount,
+            unbonding_at: agent_account.unbonding_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw the unbonding MEMO once its cooldown has passed
+    pub fn withdraw_unbonded(ctx: Context<WithdrawUnbonded>) -> Result<()> {
+        let clock = Clock::get()?;
+        let amount = ctx.accounts.agent_account.unbonding_amount;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(
+            clock.unix_timestamp >= ctx.accounts.agent_account.unbonding_at,
+            ErrorCode::UnbondCooldownActive
+        );
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.agent_bond_vault.to_account_info(),
+                    to: ctx.accounts.agent_memo_ata.to_account_info(),
+                    authority: ctx.accounts.global_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount * 10u64.pow(TOKEN_DECIMALS as u32),
+        )?;
+
+        let agent_account = &mut ctx.accounts.agent_account;
+        agent_account.unbonding_amount = 0;
+        agent_account.unbonding_at = 0;
+
+        emit!(AgentUnbonded {
+            agent: agent_account.agent,
+            amount,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Slash an agent caught creating collusive or fake connections. Admin
+    /// only. Takes from the active bond first, then from any unbonding MEMO,
+    /// and burns the slashed amount.
+    pub fn slash_agent(ctx: Context<SlashAgent>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let agent_account = &mut ctx.accounts.agent_account;
+        require!(
+            amount <= agent_account.bonded_amount + agent_account.unbonding_amount,
+            ErrorCode::InsufficientAgentBond
+        );
+
+        let from_bonded = amount.min(agent_account.bonded_amount);
+        agent_account.bonded_amount -= from_bonded;
+        agent_account.unbonding_amount -= amount - from_bonded;
+        agent_account.total_slashed += amount;
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        token::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.memo_mint.to_account_info(),
+                    from: ctx.accounts.agent_bond_vault.to_account_info(),
+                    authority: ctx.accounts.global_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount * 10u64.pow(TOKEN_DECIMALS as u32),
+        )?;
+
+        emit!(AgentSlashed {
+            agent: ctx.accounts.agent_account.agent,
+            admin: ctx.accounts.admin.key(),
+            amount,
+            bonded_amount: ctx.accounts.agent_account.bonded_amount,
+            unbonding_amount: ctx.accounts.agent_account.unbonding_amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set the guardian, a key that can pause instructions but not unpause
+    /// them. Pubkey::default() removes the guardian.
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts
+            .global_state
+            .authorize_admin(&ctx.accounts.admin.key(), ctx.remaining_accounts)?;
+
+        let global_state = &mut ctx.accounts.global_state;
+        let previous_guardian = global_state.guardian;
+        global_state.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            previous_guardian,
+            new_guardian: guardian,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Pause the instructions whose PAUSE_* bits are set in `flags`.
+    /// Callable by the admin or the guardian.
+    pub fn pause(ctx: Context<SetPause>, flags: u32) -> Result<()> {
+        let global_state = &ctx.accounts.global_state;
+        let caller = ctx.accounts.authority.key();
+        if global_state.guardian == Pubkey::default() || caller != global_state.guardian {
+            global_state.authorize_admin(&caller, ctx.remaining_accounts)?;
+        }
+        require!(flags != 0, ErrorCode::InvalidPauseFlags);
+
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.pause_flags |= flags;
+
+        emit!(Paused {
+            authority: caller,
+            flags,
+            pause_flags: global_state.pause_flags,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Unpause the instructions whose PAUSE_* bits are set in `flags`. Admin only.
+    pub fn unpause(ctx: Context<SetPause>, flags: u32) -> Result<()> {
+        let caller = ctx.accounts.authority.key();
+        ctx.accounts
+            .global_state
+            .authorize_admin(&caller, ctx.remaining_accounts)?;
+        require!(flags != 0, ErrorCode::InvalidPauseFlags);
+
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.pause_flags &= !flags;
+
+        emit!(Unpaused {
+            authority: caller,
+            flags,
+            pause_flags: global_state.pause_flags,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
         Ok(())
     }
@@ -40,7 +460,13 @@ pub mod unified_token_program {
         user_id_hash: [u8; 32],
     ) -> Result<()> {
         require!(user_id.len() <= 64, ErrorCode::UserIdTooLong);
+        require!(!user_id.is_empty(), ErrorCode::InvalidUserId);
+        require!(
+            hash_user_id(&user_id) == user_id_hash,
+            ErrorCode::InvalidUserIdHash
+        );
 
+        let initial_me_mint = ctx.accounts.global_state.config.initial_me_mint;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
 
@@ -51,13 +477,14 @@ pub mod unified_token_program {
         user_id_array[..len].copy_from_slice(&user_id_bytes[..len]);
 
         user_account.user_id = user_id_array;
+        user_account.authority = ctx.accounts.authority.key();
         user_account.me_mint = ctx.accounts.me_mint.key();
-        user_account.last_mint_time = clock.unix_timestamp;
-        user_account.daily_minted_today = INITIAL_ME_MINT;
-        user_account.total_me_minted = INITIAL_ME_MINT;
+        user_account.last_claim_time = clock.unix_timestamp;
+        user_account.total_me_minted = initial_me_mint;
         user_account.total_me_locked = 0;
         user_account.total_memo_earned = 0;
         user_account.connections_count = 0;
+        user_account.lock_positions_opened = 0;
         user_account.bump = ctx.bumps.user_account;
 
         // Mint initial ME tokens to user's ATA
@@ -78,48 +505,37 @@ pub mod unified_token_program {
                 },
                 signer_seeds,
             ),
-            INITIAL_ME_MINT * 10u64.pow(TOKEN_DECIMALS as u32),
+            initial_me_mint * 10u64.pow(TOKEN_DECIMALS as u32),
         )?;
 
         // Update global state
         let global_state = &mut ctx.accounts.global_state;
         global_state.total_users += 1;
 
-        msg!("User initialized: {}", user_id);
-        msg!("ME Mint: {}", ctx.accounts.me_mint.key());
-        msg!("Initial ME minted: {}", INITIAL_ME_MINT);
+        emit!(UserInitialized {
+            user: ctx.accounts.user_account.key(),
+            authority: ctx.accounts.authority.key(),
+            me_mint: ctx.accounts.me_mint.key(),
+            initial_me_minted: initial_me_mint,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
-    /// Mint daily ME tokens (up to 24/day)
-    pub fn mint_daily_me(
-        ctx: Context<MintDailyMe>,
-        user_id: String,
-        user_id_hash: [u8; 32],
-    ) -> Result<()> {
+    /// Mint the ME accrued since the last claim. ME accrues continuously at
+    /// config.daily_me_limit per day, up to me_carry_over_days worth.
+    pub fn claim_me(ctx: Context<ClaimMe>, user_id_hash: [u8; 32]) -> Result<()> {
+        let config = ctx.accounts.global_state.config;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
 
-        // Check if a new day has passed
-        let time_elapsed = clock.unix_timestamp - user_account.last_mint_time;
-        let days_passed = time_elapsed / DAY_IN_SECONDS;
-
-        if days_passed > 0 {
-            // Reset daily counter for new day
-            user_account.daily_minted_today = 0;
-            user_account.last_mint_time = clock.unix_timestamp;
-        }
-
-        // Check daily limit
-        require!(
-            user_account.daily_minted_today < DAILY_ME_LIMIT,
-            ErrorCode::DailyLimitReached
-        );
+        let (to_mint, accrued_seconds) =
+            config.accrued_me(clock.unix_timestamp - user_account.last_claim_time);
+        require!(to_mint > 0, ErrorCode::NothingToClaim);
 
-        // Calculate how many tokens can be minted
-        let available_to_mint = DAILY_ME_LIMIT - user_account.daily_minted_today;
-        let to_mint = available_to_mint.min(DAILY_ME_LIMIT);
+        // Only advance by the time that was paid out, so a partial token keeps accruing
+        user_account.last_claim_time += accrued_seconds;
 
         // Mint ME tokens
         let seeds = &[
@@ -142,21 +558,38 @@ pub mod unified_token_program {
             to_mint * 10u64.pow(TOKEN_DECIMALS as u32),
         )?;
 
-        user_account.daily_minted_today += to_mint;
         user_account.total_me_minted += to_mint;
 
-        msg!("Minted {} ME for {} (total: {})", to_mint, user_id, user_account.total_me_minted);
+        emit!(MeClaimed {
+            user: user_account.key(),
+            amount: to_mint,
+            last_claim_time: user_account.last_claim_time,
+            total_me_minted: user_account.total_me_minted,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
-    /// Lock ME tokens in escrow and mint MEMO tokens
+    /// Lock ME tokens in the user's escrow vault for one of the configured lock
+    /// tier durations and mint MEMO up front, scaled by that tier's multiplier
     pub fn lock_me_for_memo(
         ctx: Context<LockMeForMemo>,
+        _user_id_hash: [u8; 32],
         amount: u64,
+        duration_days: u16,
     ) -> Result<()> {
         require!(amount > 0, ErrorCode::InvalidAmount);
 
+        let config = ctx.accounts.global_state.config;
+        let multiplier_bps = config
+            .lock_multiplier_bps(duration_days)
+            .ok_or(ErrorCode::InvalidLockDuration)?;
+
+        // Whole MEMO tokens, rounded down
+        let memo_reward = amount * multiplier_bps / BPS_DENOMINATOR;
         let amount_with_decimals = amount * 10u64.pow(TOKEN_DECIMALS as u32);
+        let clock = Clock::get()?;
 
         // Transfer ME tokens from user to escrow
         token::transfer(
@@ -165,13 +598,13 @@ pub mod unified_token_program {
                 Transfer {
                     from: ctx.accounts.user_me_ata.to_account_info(),
                     to: ctx.accounts.me_escrow.to_account_info(),
-                    authority: ctx.accounts.payer.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
                 },
             ),
             amount_with_decimals,
         )?;
 
-        // Mint MEMO tokens to user (1:1 ratio for now)
+        // Mint the position's MEMO reward to the user
         let seeds = &[
             b"global_state".as_ref(),
             &[ctx.bumps.global_state],
@@ -188,33 +621,188 @@ pub mod unified_token_program {
                 },
                 signer_seeds,
             ),
-            amount_with_decimals,
+            memo_reward * 10u64.pow(TOKEN_DECIMALS as u32),
         )?;
 
         // Update user account
         let user_account = &mut ctx.accounts.user_account;
+        let position = &mut ctx.accounts.lock_position;
+        position.user = user_account.key();
+        position.position_id = user_account.lock_positions_opened;
+        position.amount = amount;
+        position.memo_reward = memo_reward;
+        position.locked_at = clock.unix_timestamp;
+        position.unlocks_at = clock.unix_timestamp + duration_days as i64 * config.day_in_seconds;
+        position.bump = ctx.bumps.lock_position;
+
+        user_account.lock_positions_opened += 1;
         user_account.total_me_locked += amount;
-        user_account.total_memo_earned += amount;
+        user_account.total_memo_earned += memo_reward;
+
+        // The vault only holds this user's ME, so it must cover everything locked
+        ctx.accounts.me_escrow.reload()?;
+        require!(
+            ctx.accounts.me_escrow.amount
+                >= user_account.total_me_locked * 10u64.pow(TOKEN_DECIMALS as u32),
+            ErrorCode::EscrowBalanceMismatch
+        );
+
+        emit!(MeLocked {
+            user: user_account.key(),
+            position: position.key(),
+            position_id: position.position_id,
+            amount,
+            duration_days,
+            memo_reward,
+            unlocks_at: position.unlocks_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw a lock position. After maturity the full ME is returned.
+    /// Before maturity the position's MEMO reward is burned from the user and
+    /// config.early_withdrawal_penalty_bps of the ME is burned from the escrow.
+    pub fn withdraw_locked_me(
+        ctx: Context<WithdrawLockedMe>,
+        _user_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let position = &ctx.accounts.lock_position;
+        let amount_with_decimals = position.amount * 10u64.pow(TOKEN_DECIMALS as u32);
+        let matured = clock.unix_timestamp >= position.unlocks_at;
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let mut penalty = 0;
+        if !matured {
+            // Hand back the MEMO minted for this position
+            token::burn(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.accounts.memo_mint.to_account_info(),
+                        from: ctx.accounts.user_memo_ata.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                    },
+                ),
+                position.memo_reward * 10u64.pow(TOKEN_DECIMALS as u32),
+            )?;
+
+            penalty = (amount_with_decimals as u128
+                * ctx.accounts.global_state.config.early_withdrawal_penalty_bps as u128
+                / BPS_DENOMINATOR as u128) as u64;
+
+            if penalty > 0 {
+                token::burn(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Burn {
+                            mint: ctx.accounts.me_mint.to_account_info(),
+                            from: ctx.accounts.me_escrow.to_account_info(),
+                            authority: ctx.accounts.global_state.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    penalty,
+                )?;
+            }
+        }
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.me_escrow.to_account_info(),
+                    to: ctx.accounts.user_me_ata.to_account_info(),
+                    authority: ctx.accounts.global_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount_with_decimals - penalty,
+        )?;
+
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.total_me_locked -= position.amount;
+        if !matured {
+            user_account.total_memo_earned -= position.memo_reward;
+        }
+
+        ctx.accounts.me_escrow.reload()?;
+        require!(
+            ctx.accounts.me_escrow.amount
+                >= user_account.total_me_locked * 10u64.pow(TOKEN_DECIMALS as u32),
+            ErrorCode::EscrowBalanceMismatch
+        );
+
+        emit!(LockedMeWithdrawn {
+            user: user_account.key(),
+            position: position.key(),
+            position_id: position.position_id,
+            amount: position.amount,
+            early: !matured,
+            memo_burned: if matured { 0 } else { position.memo_reward },
+            me_burned: penalty,
+            timestamp: clock.unix_timestamp,
+        });
 
-        msg!("Locked {} ME, minted {} MEMO", amount, amount);
         Ok(())
     }
 
-    /// Create a connection between two users
+    /// Propose a connection from user A to user B. Must be signed by a
+    /// registered agent, which is recorded on the connection. Nothing is
+    /// escrowed yet: both stakes move on acceptance, and the connection stays
+    /// Proposed, and cannot be unlocked, until B accepts.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_connection(
         ctx: Context<CreateConnection>,
         connection_id: String,
-        user_a_id: String,
-        user_b_id: String,
+        _user_a_id: String,
+        _user_b_id: String,
         pin_a_hash: [u8; 32],
         pin_b_hash: [u8; 32],
+        expiry_seconds: i64,
+        stake_amount: u64,
     ) -> Result<()> {
-        let connection = &mut ctx.accounts.connection_account;
+        require!(
+            (MIN_CONNECTION_EXPIRY..=MAX_CONNECTION_EXPIRY).contains(&expiry_seconds),
+            ErrorCode::InvalidExpiry
+        );
+        require!(
+            (MIN_CONNECTION_STAKE..=MAX_CONNECTION_STAKE).contains(&stake_amount),
+            ErrorCode::InvalidStakeAmount
+        );
+
         let clock = Clock::get()?;
 
+        // Rate limit repeat meetings: one open connection per pair at a time, and
+        // only completed connections start the cooldown, so cancelled or expired
+        // proposals cannot lock a pair out
+        let pair_cooldown = ctx.accounts.global_state.config.pair_cooldown;
+        let pair = &mut ctx.accounts.pair_account;
+        require!(pair.open_connections == 0, ErrorCode::PairCooldownActive);
+        require!(
+            pair.last_completed_at == 0
+                || clock.unix_timestamp >= pair.last_completed_at + pair_cooldown,
+            ErrorCode::PairCooldownActive
+        );
+        let user_a_key = ctx.accounts.user_a_account.key();
+        let user_b_key = ctx.accounts.user_b_account.key();
+        pair.user_low = user_a_key.min(user_b_key);
+        pair.user_high = user_a_key.max(user_b_key);
+        pair.open_connections += 1;
+        pair.bump = ctx.bumps.pair_account;
+
+        let connection = &mut ctx.accounts.connection_account;
+
         // Store connection data
         let mut connection_id_array = [0u8; 64];
-        let len = connection_id.as_bytes().len().min(64);
+        let len = connection_id.len().min(64);
         connection_id_array[..len].copy_from_slice(&connection_id.as_bytes()[..len]);
         connection.connection_id = connection_id_array;
 
@@ -225,88 +813,579 @@ pub mod unified_token_program {
         connection.user_a_unlocked = false;
         connection.user_b_unlocked = false;
         connection.created_at = clock.unix_timestamp;
+        connection.expires_at = clock.unix_timestamp + expiry_seconds;
+        connection.state = ConnectionState::Proposed;
+        connection.stake_amount = stake_amount;
+        connection.user_a_failed_attempts = 0;
+        connection.user_b_failed_attempts = 0;
+        connection.user_a_locked_until = 0;
+        connection.user_b_locked_until = 0;
+        connection.agent = ctx.accounts.agent.key();
+        connection.agent_rewarded = false;
+        connection.pair = ctx.accounts.pair_account.key();
+        connection.meeting_starts_at = 0;
+        connection.meeting_ends_at = 0;
         connection.bump = ctx.bumps.connection_account;
 
         // Update global state
         let global_state = &mut ctx.accounts.global_state;
         global_state.total_connections += 1;
+        ctx.accounts.agent_account.connections_created += 1;
 
-        msg!("Connection created: {}", connection_id);
-        msg!("User A: {}", user_a_id);
-        msg!("User B: {}", user_b_id);
+        emit!(ConnectionCreated {
+            connection: connection.key(),
+            user_a: connection.user_a,
+            user_b: connection.user_b,
+            stake_amount,
+            expires_at: connection.expires_at,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
-    /// Unlock a connection with PIN
-    pub fn unlock_connection(
-        ctx: Context<UnlockConnection>,
-        user_id_hash: [u8; 32],
-        pin: [u8; 4],
+    /// User B accepts a proposed connection and both stakes are escrowed
+    /// together, so A's authority co-signs. Only then can either side unlock
+    /// with a PIN.
+    pub fn accept_connection(
+        ctx: Context<AcceptConnection>,
+        _user_b_id_hash: [u8; 32],
     ) -> Result<()> {
-        // Get the user key before borrowing mutably
-        let user_key = ctx.accounts.user_account.key();
+        let clock = Clock::get()?;
+        let connection = &mut ctx.accounts.connection_account;
+        require!(
+            clock.unix_timestamp < connection.expires_at,
+            ErrorCode::ConnectionExpired
+        );
+        let stake = connection.stake_amount * 10u64.pow(TOKEN_DECIMALS as u32);
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_a_me_ata.to_account_info(),
+                    to: ctx.accounts.stake_vault_a.to_account_info(),
+                    authority: ctx.accounts.user_a_authority.to_account_info(),
+                },
+            ),
+            stake,
+        )?;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user_b_me_ata.to_account_info(),
+                    to: ctx.accounts.stake_vault_b.to_account_info(),
+                    authority: ctx.accounts.user_b_authority.to_account_info(),
+                },
+            ),
+            stake,
+        )?;
+
+        connection.transition(ConnectionState::Accepted)?;
 
+        emit!(ConnectionAccepted {
+            connection: connection.key(),
+            user_b: connection.user_b,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// User B declines a proposed connection, which is cancelled. Nothing was
+    /// escrowed yet, so there is nothing to refund.
+    pub fn decline_connection(
+        ctx: Context<DeclineConnection>,
+        _user_b_id_hash: [u8; 32],
+    ) -> Result<()> {
         let connection = &mut ctx.accounts.connection_account;
-        let user_account = &mut ctx.accounts.user_account;
+        connection.transition(ConnectionState::Cancelled)?;
+        ctx.accounts.pair_account.close_connection();
 
-        // Hash the submitted PIN using SHA256
-        let mut hasher = Sha256::new();
-        hasher.update(&pin);
-        let result = hasher.finalize();
-        let pin_hash: [u8; 32] = result.into();
+        emit!(ConnectionDeclined {
+            connection: connection.key(),
+            user_b: connection.user_b,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        // Determine which user is submitting and verify PIN
-        let is_user_a = user_key == connection.user_a;
-        let is_user_b = user_key == connection.user_b;
+        Ok(())
+    }
 
-        require!(is_user_a || is_user_b, ErrorCode::UnauthorizedUser);
+    /// Cancel a connection before either user has unlocked. Callable by either
+    /// user, named by `canceller_id_hash` since both may share one authority.
+    /// Proposals hold no stake; once accepted, cancel_penalty_bps of the
+    /// cancelling user's stake is burned and the rest of both stakes is refunded.
+    pub fn cancel_connection(
+        ctx: Context<CancelConnection>,
+        user_a_id_hash: [u8; 32],
+        _user_b_id_hash: [u8; 32],
+        canceller_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let connection = &mut ctx.accounts.connection_account;
+        let was_accepted = connection.state != ConnectionState::Proposed;
+        connection.transition(ConnectionState::Cancelled)?;
+        ctx.accounts.pair_account.close_connection();
 
-        if is_user_a {
-            // User A unlocks with User B's PIN
-            require!(pin_hash == connection.pin_b_hash, ErrorCode::InvalidPin);
-            require!(!connection.user_a_unlocked, ErrorCode::AlreadyUnlocked);
-            connection.user_a_unlocked = true;
+        let cancelled_by_a = canceller_id_hash == user_a_id_hash;
+        let (cancelled_by, canceller_vault, canceller_me_mint) = if cancelled_by_a {
+            (connection.user_a, &ctx.accounts.stake_vault_a, &ctx.accounts.user_a_me_mint)
         } else {
-            // User B unlocks with User A's PIN
-            require!(pin_hash == connection.pin_a_hash, ErrorCode::InvalidPin);
-            require!(!connection.user_b_unlocked, ErrorCode::AlreadyUnlocked);
-            connection.user_b_unlocked = true;
-        }
+            (connection.user_b, &ctx.accounts.stake_vault_b, &ctx.accounts.user_b_me_mint)
+        };
 
-        // Mint MEMO reward
         let seeds = &[
             b"global_state".as_ref(),
             &[ctx.bumps.global_state],
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let reward_amount = CONNECTION_MEMO_REWARD * 10u64.pow(TOKEN_DECIMALS as u32);
+        let penalty_burned = if was_accepted {
+            (canceller_vault.amount as u128
+                * ctx.accounts.global_state.config.cancel_penalty_bps as u128
+                / BPS_DENOMINATOR as u128) as u64
+        } else {
+            0
+        };
+
+        if penalty_burned > 0 {
+            token::burn(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: canceller_me_mint.to_account_info(),
+                        from: canceller_vault.to_account_info(),
+                        authority: ctx.accounts.global_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                penalty_burned,
+            )?;
+            ctx.accounts.stake_vault_a.reload()?;
+            ctx.accounts.stake_vault_b.reload()?;
+        }
 
-        token::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.memo_mint.to_account_info(),
-                    to: ctx.accounts.user_memo_ata.to_account_info(),
-                    authority: ctx.accounts.global_state.to_account_info(),
-                },
-                signer_seeds,
-            ),
-            reward_amount,
+        let stake_refunded_a = ctx.accounts.stake_vault_a.amount;
+        let stake_refunded_b = ctx.accounts.stake_vault_b.amount;
+        release_stake(
+            &ctx.accounts.token_program,
+            &ctx.accounts.stake_vault_a,
+            &ctx.accounts.user_a_me_ata,
+            &ctx.accounts.global_state,
+            signer_seeds,
         )?;
 
-        // Update user account
-        user_account.total_memo_earned += CONNECTION_MEMO_REWARD;
+        release_stake(
+            &ctx.accounts.token_program,
+            &ctx.accounts.stake_vault_b,
+            &ctx.accounts.user_b_me_ata,
+            &ctx.accounts.global_state,
+            signer_seeds,
+        )?;
+
+        emit!(ConnectionCancelled {
+            connection: ctx.accounts.connection_account.key(),
+            cancelled_by,
+            stake_refunded_a,
+            stake_refunded_b,
+            penalty_burned,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Set or move the meeting window of an accepted connection. Needs both
+    /// users' signatures. The unlock deadline moves to the end of the window.
+    pub fn reschedule_connection(
+        ctx: Context<RescheduleConnection>,
+        _user_a_id_hash: [u8; 32],
+        _user_b_id_hash: [u8; 32],
+        meeting_starts_at: i64,
+        meeting_ends_at: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            meeting_starts_at >= clock.unix_timestamp
+                && meeting_ends_at > meeting_starts_at
+                && meeting_ends_at <= clock.unix_timestamp + MAX_CONNECTION_EXPIRY,
+            ErrorCode::InvalidMeetingWindow
+        );
+
+        let connection = &mut ctx.accounts.connection_account;
+        connection.transition(ConnectionState::Scheduled)?;
+        connection.meeting_starts_at = meeting_starts_at;
+        connection.meeting_ends_at = meeting_ends_at;
+        connection.expires_at = meeting_ends_at;
+
+        emit!(ConnectionRescheduled {
+            connection: connection.key(),
+            meeting_starts_at,
+            meeting_ends_at,
+            expires_at: connection.expires_at,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Unlock a connection with the other user's PIN and the salt of its commitment.
+    /// A wrong PIN is recorded against the submitting side rather than failing the
+    /// transaction, so the attempt counter persists; after config.max_pin_attempts
+    /// that side is locked out for config.pin_lockout_seconds.
+    pub fn unlock_connection(
+        ctx: Context<UnlockConnection>,
+        _user_id_hash: [u8; 32],
+        pin: [u8; 4],
+        salt: [u8; 32],
+    ) -> Result<()> {
+        // Get the user key before borrowing mutably
+        let user_key = ctx.accounts.user_account.key();
+
+        let connection = &mut ctx.accounts.connection_account;
+        let user_account = &mut ctx.accounts.user_account;
+        let config = ctx.accounts.global_state.config;
+        let clock = Clock::get()?;
+
+        require!(
+            clock.unix_timestamp < connection.expires_at,
+            ErrorCode::ConnectionExpired
+        );
+
+        let connection_key = connection.key();
+
+        // Determine which user is submitting and verify PIN
+        let is_user_a = user_key == connection.user_a;
+        let is_user_b = user_key == connection.user_b;
+
+        require!(is_user_a || is_user_b, ErrorCode::UnauthorizedUser);
+
+        // User A unlocks with User B's PIN and vice versa
+        let (already_unlocked, locked_until, pin_owner, expected_hash) = if is_user_a {
+            (connection.user_a_unlocked, connection.user_a_locked_until, connection.user_b, connection.pin_b_hash)
+        } else {
+            (connection.user_b_unlocked, connection.user_b_locked_until, connection.user_a, connection.pin_a_hash)
+        };
+
+        require!(!already_unlocked, ErrorCode::AlreadyUnlocked);
+        require!(clock.unix_timestamp >= locked_until, ErrorCode::PinLockedOut);
+
+        let pin_hash = pin_commitment(&salt, &connection_key, &pin_owner, &pin);
+
+        if pin_hash != expected_hash {
+            let (failed_attempts, locked_until) =
+                connection.record_failed_pin(is_user_a, clock.unix_timestamp, &config);
+
+            emit!(PinAttemptFailed {
+                connection: connection_key,
+                user: user_key,
+                failed_attempts,
+                max_attempts: config.max_pin_attempts,
+                locked_until,
+                timestamp: clock.unix_timestamp,
+            });
+
+            return Ok(());
+        }
+
+        if is_user_a {
+            connection.user_a_unlocked = true;
+            connection.user_a_failed_attempts = 0;
+        } else {
+            connection.user_b_unlocked = true;
+            connection.user_b_failed_attempts = 0;
+        }
+        if connection.user_a_unlocked && connection.user_b_unlocked {
+            connection.transition(ConnectionState::Completed)?;
+        } else {
+            connection.transition(ConnectionState::PartiallyUnlocked)?;
+        }
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        // Return the unlocking user's stake
+        let stake_returned = ctx.accounts.stake_vault.amount;
+        release_stake(
+            &ctx.accounts.token_program,
+            &ctx.accounts.stake_vault,
+            &ctx.accounts.user_me_ata,
+            &ctx.accounts.global_state,
+            signer_seeds,
+        )?;
+
+        // Mint MEMO reward, reduced for pairs that have met before
+        let pair_completed = ctx.accounts.pair_account.connections_completed;
+        let memo_reward = config.pair_reward(config.connection_memo_reward, pair_completed);
+        let reward_amount = memo_reward * 10u64.pow(TOKEN_DECIMALS as u32);
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.memo_mint.to_account_info(),
+                    to: ctx.accounts.user_memo_ata.to_account_info(),
+                    authority: ctx.accounts.global_state.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            reward_amount,
+        )?;
+
+        // Update user account
+        user_account.total_memo_earned += memo_reward;
         user_account.connections_count += 1;
 
-        msg!("Connection unlocked! Rewarded {} MEMO", CONNECTION_MEMO_REWARD);
-        msg!("Both unlocked: {}", connection.user_a_unlocked && connection.user_b_unlocked);
+        emit!(ConnectionUnlocked {
+            connection: connection_key,
+            user: user_key,
+            memo_reward,
+            stake_returned,
+            timestamp: clock.unix_timestamp,
+        });
+
+        // The second unlock pays the agent that brokered the connection, once.
+        // A revoked agent is still credited with the completion but not paid.
+        if connection.state == ConnectionState::Completed && !connection.agent_rewarded {
+            let agent_account = &mut ctx.accounts.agent_account;
+            let agent_reward = if agent_account.active {
+                config.pair_reward(config.agent_memo_reward, pair_completed)
+            } else {
+                0
+            };
+            connection.agent_rewarded = true;
+            agent_account.connections_completed += 1;
+
+            let pair = &mut ctx.accounts.pair_account;
+            pair.connections_completed += 1;
+            pair.last_completed_at = clock.unix_timestamp;
+            pair.close_connection();
+
+            if agent_reward > 0 {
+                token::mint_to(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        MintTo {
+                            mint: ctx.accounts.memo_mint.to_account_info(),
+                            to: ctx.accounts.agent_memo_ata.to_account_info(),
+                            authority: ctx.accounts.global_state.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    agent_reward * 10u64.pow(TOKEN_DECIMALS as u32),
+                )?;
+            }
+
+            emit!(ConnectionCompleted {
+                connection: connection_key,
+                user_a: connection.user_a,
+                user_b: connection.user_b,
+                agent: connection.agent,
+                agent_reward,
+                pair_connections_completed: ctx.accounts.pair_account.connections_completed,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Expire a connection whose deadline passed before either user unlocked.
+    /// Permissionless so any cranker can clean up abandoned connections.
+    /// Both stakes are returned to their owners.
+    pub fn expire_connection(
+        ctx: Context<ExpireConnection>,
+        _user_a_id_hash: [u8; 32],
+        _user_b_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let connection = &mut ctx.accounts.connection_account;
+        let clock = Clock::get()?;
+
+        require!(
+            clock.unix_timestamp >= connection.expires_at,
+            ErrorCode::ConnectionNotExpired
+        );
+
+        connection.transition(ConnectionState::Expired)?;
+        ctx.accounts.pair_account.close_connection();
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let stake_returned_a = ctx.accounts.stake_vault_a.amount;
+        let stake_returned_b = ctx.accounts.stake_vault_b.amount;
+        release_stake(
+            &ctx.accounts.token_program,
+            &ctx.accounts.stake_vault_a,
+            &ctx.accounts.user_a_me_ata,
+            &ctx.accounts.global_state,
+            signer_seeds,
+        )?;
+
+        release_stake(
+            &ctx.accounts.token_program,
+            &ctx.accounts.stake_vault_b,
+            &ctx.accounts.user_b_me_ata,
+            &ctx.accounts.global_state,
+            signer_seeds,
+        )?;
+
+        emit!(ConnectionExpired {
+            connection: connection.key(),
+            user_a: connection.user_a,
+            user_b: connection.user_b,
+            stake_returned_a,
+            stake_returned_b,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Settle a connection after its deadline when only one user unlocked.
+    /// The user who showed up receives config.no_show_forfeit_bps of the absent
+    /// user's stake and the remainder is burned. Permissionless.
+    pub fn settle_no_show(
+        ctx: Context<SettleNoShow>,
+        _present_user_id_hash: [u8; 32],
+        _absent_user_id_hash: [u8; 32],
+    ) -> Result<()> {
+        let connection = &mut ctx.accounts.connection_account;
+        let clock = Clock::get()?;
+
+        require!(
+            clock.unix_timestamp >= connection.expires_at,
+            ErrorCode::ConnectionNotExpired
+        );
+
+        let present_key = ctx.accounts.present_user_account.key();
+        let present_unlocked = if present_key == connection.user_a {
+            connection.user_a_unlocked
+        } else {
+            connection.user_b_unlocked
+        };
+        require!(present_unlocked, ErrorCode::NotUnlocked);
+
+        connection.transition(ConnectionState::Expired)?;
+        ctx.accounts.pair_account.close_connection();
+
+        let forfeited = ctx.accounts.absent_stake_vault.amount;
+        let to_present = (forfeited as u128
+            * ctx.accounts.global_state.config.no_show_forfeit_bps as u128
+            / BPS_DENOMINATOR as u128) as u64;
+        let to_burn = forfeited - to_present;
+
+        let seeds = &[
+            b"global_state".as_ref(),
+            &[ctx.bumps.global_state],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        if to_present > 0 {
+            token::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.absent_stake_vault.to_account_info(),
+                        to: ctx.accounts.forfeit_recipient.to_account_info(),
+                        authority: ctx.accounts.global_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                to_present,
+            )?;
+        }
+
+        if to_burn > 0 {
+            token::burn(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.accounts.absent_me_mint.to_account_info(),
+                        from: ctx.accounts.absent_stake_vault.to_account_info(),
+                        authority: ctx.accounts.global_state.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                to_burn,
+            )?;
+        }
+
+        emit!(NoShowSettled {
+            connection: connection.key(),
+            present_user: present_key,
+            absent_user: ctx.accounts.absent_user_account.key(),
+            forfeited,
+            paid_to_present: to_present,
+            burned: to_burn,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 }
 
+// ============================================================================
+// Helpers
+// ============================================================================
+
+/// Hash a platform-qualified user ID (e.g. "telegram:user123") under the
+/// program's domain prefix. Every user PDA is derived from this hash.
+fn hash_user_id(user_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(USER_ID_DOMAIN);
+    hasher.update(user_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Commitment stored for a connection PIN:
+/// sha256(domain || salt || connection || pin owner || pin).
+/// Each PIN owner generates their salt on their own device and commits to it
+/// there; only the commitment goes to the agent. The salt travels only with
+/// the PIN, from its owner to the other user at the meeting, and is revealed
+/// on unlock. The agent never sees it, so it cannot brute-force the hash.
+fn pin_commitment(salt: &[u8; 32], connection: &Pubkey, owner: &Pubkey, pin: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(PIN_COMMITMENT_DOMAIN);
+    hasher.update(salt);
+    hasher.update(connection.as_ref());
+    hasher.update(owner.as_ref());
+    hasher.update(pin);
+    hasher.finalize().into()
+}
+
+/// Move the full balance of a connection stake vault back to a user's ME account
+fn release_stake<'info>(
+    token_program: &Program<'info, Token>,
+    vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    global_state: &Account<'info, GlobalState>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if vault.amount == 0 {
+        return Ok(());
+    }
+
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: vault.to_account_info(),
+                to: to.to_account_info(),
+                authority: global_state.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        vault.amount,
+    )
+}
+
 // ============================================================================
 // Account Structures
 // ============================================================================
@@ -314,177 +1393,1497 @@ pub mod unified_token_program {
 #[account]
 pub struct GlobalState {
     pub memo_mint: Pubkey,           // Global MEMO token mint (32 bytes)
-    pub me_escrow: Pubkey,           // Escrow account for locked ME tokens (32 bytes)
     pub admin: Pubkey,               // Admin pubkey (32 bytes)
+    pub pending_admin: Pubkey,       // Proposed admin, default if none (32 bytes)
+    pub admin_multisig: AdminMultisig, // Optional M-of-N admin (AdminMultisig::SPACE bytes)
+    pub guardian: Pubkey,            // Can pause but not unpause, default if none (32 bytes)
+    pub pause_flags: u32,            // PAUSE_* bits of paused instructions (4 bytes)
     pub total_users: u64,            // Total registered users (8 bytes)
     pub total_connections: u64,      // Total connections created (8 bytes)
+    pub config: ProgramConfig,       // Tokenomics parameters (ProgramConfig::SPACE bytes)
+}
+
+impl GlobalState {
+    fn is_paused(&self, flag: u32) -> bool {
+        self.pause_flags & flag != 0
+    }
+
+    /// Check that an admin instruction is authorized: by the admin key, or by
+    /// `threshold` multisig members when a multisig is set. Co-signers beyond
+    /// `admin` are passed as signing remaining accounts.
+    fn authorize_admin(&self, admin: &Pubkey, co_signers: &[AccountInfo]) -> Result<()> {
+        let multisig = &self.admin_multisig;
+        if multisig.threshold == 0 {
+            require_keys_eq!(*admin, self.admin, ErrorCode::UnauthorizedAdmin);
+            return Ok(());
+        }
+
+        let approvals = multisig.signers[..multisig.signer_count as usize]
+            .iter()
+            .filter(|member| {
+                *member == admin
+                    || co_signers.iter().any(|acc| acc.is_signer && acc.key == *member)
+            })
+            .count();
+        require!(
+            approvals >= multisig.threshold as usize,
+            ErrorCode::UnauthorizedAdmin
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdminMultisig {
+    pub threshold: u8,                            // Required approvals, 0 if disabled (1 byte)
+    pub signer_count: u8,                         // Members in use (1 byte)
+    pub signers: [Pubkey; MAX_ADMIN_SIGNERS],     // Members (5 * 32 bytes)
+}
+
+impl AdminMultisig {
+    pub const SPACE: usize = 1 + 1 + MAX_ADMIN_SIGNERS * 32;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockTier {
+    pub duration_days: u16,         // Lock duration (2 bytes)
+    pub multiplier_bps: u64,        // MEMO minted per ME locked, in bps (8 bytes)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramConfig {
+    pub initial_me_mint: u64,                    // ME minted on user creation (8 bytes)
+    pub daily_me_limit: u64,                     // ME mintable per day (8 bytes)
+    pub day_in_seconds: i64,                     // Length of a minting day (8 bytes)
+    pub me_carry_over_days: u64,                 // Days of unclaimed ME that can accrue (8 bytes)
+    pub connection_memo_reward: u64,             // MEMO per connection unlock (8 bytes)
+    pub agent_memo_reward: u64,                  // MEMO to the agent per completed connection (8 bytes)
+    pub min_agent_bond: u64,                     // MEMO bond required to create connections (8 bytes)
+    pub agent_unbond_cooldown: i64,              // Seconds before unbonded MEMO can be withdrawn (8 bytes)
+    pub pair_cooldown: i64,                      // Seconds after a pair completes before it can connect again (8 bytes)
+    pub pair_reward_decay_bps: u64,              // Reward kept per previous pair meeting (8 bytes)
+    pub cancel_penalty_bps: u64,                 // Canceller's stake burned after acceptance (8 bytes)
+    pub no_show_forfeit_bps: u64,                // No-show's stake paid to the present user (8 bytes)
+    pub early_withdrawal_penalty_bps: u64,       // ME burned on early lock withdrawal (8 bytes)
+    pub max_pin_attempts: u8,                    // Wrong PINs before a side is locked out (1 byte)
+    pub pin_lockout_seconds: i64,                // Length of a PIN lockout (8 bytes)
+    pub lock_tiers: [LockTier; LOCK_TIER_COUNT], // Lock durations and multipliers (3 * 10 bytes)
+}
+
+impl ProgramConfig {
+    pub const SPACE: usize = 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + LOCK_TIER_COUNT * (2 + 8);
+
+    fn validate(&self) -> Result<()> {
+        require!(self.initial_me_mint <= MAX_INITIAL_ME_MINT, ErrorCode::InvalidConfig);
+        require!(
+            (1..=MAX_DAILY_ME_LIMIT).contains(&self.daily_me_limit),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            (MIN_DAY_IN_SECONDS..=MAX_DAY_IN_SECONDS).contains(&self.day_in_seconds),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            (1..=MAX_ME_CARRY_OVER_DAYS).contains(&self.me_carry_over_days),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.connection_memo_reward <= MAX_CONNECTION_MEMO_REWARD,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.agent_memo_reward <= MAX_CONNECTION_MEMO_REWARD,
+            ErrorCode::InvalidConfig
+        );
+        require!(self.min_agent_bond <= MAX_MIN_AGENT_BOND, ErrorCode::InvalidConfig);
+        require!(
+            (MIN_AGENT_UNBOND_COOLDOWN..=MAX_AGENT_UNBOND_COOLDOWN).contains(&self.agent_unbond_cooldown),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            (0..=MAX_PAIR_COOLDOWN).contains(&self.pair_cooldown),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.pair_reward_decay_bps <= BPS_DENOMINATOR,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.cancel_penalty_bps <= BPS_DENOMINATOR,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.no_show_forfeit_bps <= BPS_DENOMINATOR,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.early_withdrawal_penalty_bps <= BPS_DENOMINATOR,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            (1..=MAX_PIN_ATTEMPTS).contains(&self.max_pin_attempts),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            (MIN_PIN_LOCKOUT_SECONDS..=MAX_PIN_LOCKOUT_SECONDS).contains(&self.pin_lockout_seconds),
+            ErrorCode::InvalidConfig
+        );
+
+        // Tiers must be distinct, ascending and pay something
+        let mut previous_days = 0;
+        for tier in self.lock_tiers.iter() {
+            require!(
+                tier.duration_days > previous_days && tier.duration_days <= MAX_LOCK_DURATION_DAYS,
+                ErrorCode::InvalidConfig
+            );
+            require!(
+                (1..=MAX_LOCK_MULTIPLIER_BPS).contains(&tier.multiplier_bps),
+                ErrorCode::InvalidConfig
+            );
+            previous_days = tier.duration_days;
+        }
+
+        Ok(())
+    }
+
+    /// Reward for a pair that has already completed `previous_meetings`
+    /// connections: the full reward the first time, then scaled down by
+    /// pair_reward_decay_bps for each earlier meeting (whole tokens, rounded down).
+    fn pair_reward(&self, reward: u64, previous_meetings: u64) -> u64 {
+        let mut reward = reward as u128;
+        for _ in 0..previous_meetings {
+            if reward == 0 {
+                break;
+            }
+            reward = reward * self.pair_reward_decay_bps as u128 / BPS_DENOMINATOR as u128;
+        }
+        reward as u64
+    }
+
+    /// Whole ME accrued over `elapsed` seconds at daily_me_limit per day,
+    /// capped at me_carry_over_days days, and the seconds of accrual it pays for.
+    /// Below the cap that is rounded up to never pay ahead; at the cap it is all
+    /// of `elapsed`, forfeiting the excess.
+    fn accrued_me(&self, elapsed: i64) -> (u64, i64) {
+        let elapsed = elapsed.max(0);
+        let limit = self.daily_me_limit as u128;
+        let day = self.day_in_seconds as u128;
+        let cap = self.daily_me_limit * self.me_carry_over_days;
+
+        let accrued = (elapsed as u128 * limit / day) as u64;
+        if accrued >= cap {
+            return (cap, elapsed);
+        }
+        let paid_seconds = (accrued as u128 * day).div_ceil(limit) as i64;
+        (accrued, paid_seconds)
+    }
+
+    fn lock_multiplier_bps(&self, duration_days: u16) -> Option<u64> {
+        self.lock_tiers
+            .iter()
+            .find(|tier| tier.duration_days == duration_days)
+            .map(|tier| tier.multiplier_bps)
+    }
+}
+
+impl Default for ProgramConfig {
+    fn default() -> Self {
+        Self {
+            initial_me_mint: DEFAULT_INITIAL_ME_MINT,
+            daily_me_limit: DEFAULT_DAILY_ME_LIMIT,
+            day_in_seconds: DEFAULT_DAY_IN_SECONDS,
+            me_carry_over_days: DEFAULT_ME_CARRY_OVER_DAYS,
+            connection_memo_reward: DEFAULT_CONNECTION_MEMO_REWARD,
+            agent_memo_reward: DEFAULT_AGENT_MEMO_REWARD,
+            min_agent_bond: DEFAULT_MIN_AGENT_BOND,
+            agent_unbond_cooldown: DEFAULT_AGENT_UNBOND_COOLDOWN,
+            pair_cooldown: DEFAULT_PAIR_COOLDOWN,
+            pair_reward_decay_bps: DEFAULT_PAIR_REWARD_DECAY_BPS,
+            cancel_penalty_bps: DEFAULT_CANCEL_PENALTY_BPS,
+            no_show_forfeit_bps: DEFAULT_NO_SHOW_FORFEIT_BPS,
+            early_withdrawal_penalty_bps: DEFAULT_EARLY_WITHDRAWAL_PENALTY_BPS,
+            max_pin_attempts: DEFAULT_MAX_PIN_ATTEMPTS,
+            pin_lockout_seconds: DEFAULT_PIN_LOCKOUT_SECONDS,
+            lock_tiers: DEFAULT_LOCK_TIERS.map(|(duration_days, multiplier_bps)| LockTier {
+                duration_days,
+                multiplier_bps,
+            }),
+        }
+    }
 }
 
 #[account]
 pub struct UserAccount {
     pub user_id: [u8; 64],          // User identifier (64 bytes)
+    pub authority: Pubkey,          // Wallet that controls this user (32 bytes)
     pub me_mint: Pubkey,            // Personal ME token mint (32 bytes)
-    pub last_mint_time: i64,        // Unix timestamp of last mint (8 bytes)
-    pub daily_minted_today: u64,    // Amount minted today (8 bytes)
+    pub last_claim_time: i64,       // ME has been paid out up to here (8 bytes)
     pub total_me_minted: u64,       // Total lifetime ME minted (8 bytes)
-    pub total_me_locked: u64,       // Total ME locked in escrow (8 bytes)
+    pub total_me_locked: u64,       // Total ME locked in the escrow vault (8 bytes)
     pub total_memo_earned: u64,     // Total MEMO earned (8 bytes)
     pub connections_count: u64,     // Number of connections made (8 bytes)
+    pub lock_positions_opened: u64, // Lock positions ever opened, next position ID (8 bytes)
+    pub bump: u8,                   // PDA bump seed (1 byte)
+}
+
+#[account]
+pub struct LockPosition {
+    pub user: Pubkey,               // Owning UserAccount (32 bytes)
+    pub position_id: u64,           // Sequential ID per user (8 bytes)
+    pub amount: u64,                // ME locked (8 bytes)
+    pub memo_reward: u64,           // MEMO minted for this position (8 bytes)
+    pub locked_at: i64,             // Unix timestamp (8 bytes)
+    pub unlocks_at: i64,            // Maturity timestamp (8 bytes)
+    pub bump: u8,                   // PDA bump seed (1 byte)
+}
+
+#[account]
+pub struct AgentAccount {
+    pub agent: Pubkey,              // Agent wallet (32 bytes)
+    pub active: bool,               // Allowed to create connections? (1 byte)
+    pub registered_at: i64,         // Last (re-)registration timestamp (8 bytes)
+    pub connections_created: u64,   // Connections created by this agent (8 bytes)
+    pub connections_completed: u64, // Of those, fully unlocked (8 bytes)
+    pub bonded_amount: u64,         // MEMO bonded and counting toward min_agent_bond (8 bytes)
+    pub unbonding_amount: u64,      // MEMO waiting out the unbond cooldown (8 bytes)
+    pub unbonding_at: i64,          // When the unbonding MEMO can be withdrawn (8 bytes)
+    pub total_slashed: u64,         // Lifetime MEMO slashed (8 bytes)
+    pub bump: u8,                   // PDA bump seed (1 byte)
+}
+
+#[account]
+pub struct PairAccount {
+    pub user_low: Pubkey,           // Lower of the two UserAccount keys (32 bytes)
+    pub user_high: Pubkey,          // Higher of the two UserAccount keys (32 bytes)
+    pub connections_completed: u64, // Fully unlocked connections between them (8 bytes)
+    pub last_completed_at: i64,     // Unix timestamp of the last completion (8 bytes)
+    pub open_connections: u64,      // Connections not yet completed, cancelled or expired (8 bytes)
     pub bump: u8,                   // PDA bump seed (1 byte)
 }
 
+impl PairAccount {
+    /// Release the pair's open connection once it is completed, cancelled or expired
+    fn close_connection(&mut self) {
+        self.open_connections = self.open_connections.saturating_sub(1);
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Proposed,          // Created, waiting for the other user to accept
+    Accepted,          // Both users agreed, stakes escrowed, PINs unlockable
+    Scheduled,         // Accepted with an agreed meeting window
+    PartiallyUnlocked, // One user has unlocked
+    Completed,         // Both users have unlocked
+    Expired,           // Deadline passed (stakes refunded or no-show settled)
+    Cancelled,         // Withdrawn or declined before any unlock
+    Disputed,          // Reserved for dispute resolution; no instruction enters it yet
+}
+
+impl ConnectionState {
+    /// The single source of truth for the connection lifecycle. Disputed has
+    /// no transitions until dispute instructions exist.
+    fn can_transition_to(self, next: ConnectionState) -> bool {
+        use ConnectionState::*;
+        matches!(
+            (self, next),
+            (Proposed, Accepted | Cancelled | Expired)
+                | (Accepted, Scheduled | PartiallyUnlocked | Cancelled | Expired)
+                | (Scheduled, Scheduled | PartiallyUnlocked | Cancelled | Expired)
+                | (PartiallyUnlocked, Completed | Expired)
+        )
+    }
+}
+
 #[account]
 pub struct ConnectionAccount {
     pub connection_id: [u8; 64],    // Connection identifier (64 bytes)
     pub user_a: Pubkey,             // User A pubkey (32 bytes)
     pub user_b: Pubkey,             // User B pubkey (32 bytes)
-    pub pin_a_hash: [u8; 32],       // Hash of PIN for User A (32 bytes)
-    pub pin_b_hash: [u8; 32],       // Hash of PIN for User B (32 bytes)
+    pub pin_a_hash: [u8; 32],       // Salted commitment of User A's PIN (32 bytes)
+    pub pin_b_hash: [u8; 32],       // Salted commitment of User B's PIN (32 bytes)
     pub user_a_unlocked: bool,      // Has User A unlocked? (1 byte)
     pub user_b_unlocked: bool,      // Has User B unlocked? (1 byte)
     pub created_at: i64,            // Unix timestamp (8 bytes)
+    pub expires_at: i64,            // Unlock deadline (8 bytes)
+    pub state: ConnectionState,     // Lifecycle state (1 byte)
+    pub stake_amount: u64,          // ME staked by each user (8 bytes)
+    pub user_a_failed_attempts: u8, // Wrong PINs submitted by User A (1 byte)
+    pub user_b_failed_attempts: u8, // Wrong PINs submitted by User B (1 byte)
+    pub user_a_locked_until: i64,   // User A PIN lockout end (8 bytes)
+    pub user_b_locked_until: i64,   // User B PIN lockout end (8 bytes)
+    pub agent: Pubkey,              // Agent that brokered the connection (32 bytes)
+    pub agent_rewarded: bool,       // Has the agent been paid? (1 byte)
+    pub pair: Pubkey,               // PairAccount of the two users (32 bytes)
+    pub meeting_starts_at: i64,     // Agreed meeting window start, 0 if unscheduled (8 bytes)
+    pub meeting_ends_at: i64,       // Agreed meeting window end, 0 if unscheduled (8 bytes)
     pub bump: u8,                   // PDA bump seed (1 byte)
 }
 
-// ============================================================================
-// Instruction Contexts
-// ============================================================================
+impl ConnectionAccount {
+    /// Move to `next`, rejecting anything ConnectionState::can_transition_to does not allow
+    fn transition(&mut self, next: ConnectionState) -> Result<()> {
+        require!(
+            self.state.can_transition_to(next),
+            ErrorCode::InvalidStateTransition
+        );
+        self.state = next;
+        Ok(())
+    }
+
+    /// Record a wrong PIN for one side, starting a lockout once
+    /// config.max_pin_attempts is reached. Returns the failed attempt count and
+    /// the lockout end.
+    fn record_failed_pin(&mut self, is_user_a: bool, now: i64, config: &ProgramConfig) -> (u8, i64) {
+        let (attempts, locked_until) = if is_user_a {
+            (&mut self.user_a_failed_attempts, &mut self.user_a_locked_until)
+        } else {
+            (&mut self.user_b_failed_attempts, &mut self.user_b_locked_until)
+        };
+
+        *attempts += 1;
+        let failed_attempts = *attempts;
+        if failed_attempts >= config.max_pin_attempts {
+            *locked_until = now + config.pin_lockout_seconds;
+            *attempts = 0;
+        }
+
+        (failed_attempts, *locked_until)
+    }
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+#[event]
+pub struct GlobalInitialized {
+    pub admin: Pubkey,
+    pub memo_mint: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub old_config: ProgramConfig,
+    pub new_config: ProgramConfig,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminMultisigUpdated {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentRegistered {
+    pub agent: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentRevoked {
+    pub agent: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentBonded {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub bonded_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentUnbondRequested {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub unbonding_amount: u64,
+    pub unbonding_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentUnbonded {
+    pub agent: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AgentSlashed {
+    pub agent: Pubkey,
+    pub admin: Pubkey,
+    pub amount: u64,
+    pub bonded_amount: u64,
+    pub unbonding_amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub previous_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Paused {
+    pub authority: Pubkey,
+    pub flags: u32,
+    pub pause_flags: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Unpaused {
+    pub authority: Pubkey,
+    pub flags: u32,
+    pub pause_flags: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserInitialized {
+    pub user: Pubkey,
+    pub authority: Pubkey,
+    pub me_mint: Pubkey,
+    pub initial_me_minted: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MeClaimed {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub last_claim_time: i64,
+    pub total_me_minted: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MeLocked {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub position_id: u64,
+    pub amount: u64,
+    pub duration_days: u16,
+    pub memo_reward: u64,
+    pub unlocks_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LockedMeWithdrawn {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub position_id: u64,
+    pub amount: u64,
+    pub early: bool,
+    pub memo_burned: u64,
+    pub me_burned: u64,             // ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionCreated {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub stake_amount: u64,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionAccepted {
+    pub connection: Pubkey,
+    pub user_b: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionDeclined {
+    pub connection: Pubkey,
+    pub user_b: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionCancelled {
+    pub connection: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub stake_refunded_a: u64,      // ME base units
+    pub stake_refunded_b: u64,      // ME base units
+    pub penalty_burned: u64,        // ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionRescheduled {
+    pub connection: Pubkey,
+    pub meeting_starts_at: i64,
+    pub meeting_ends_at: i64,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionUnlocked {
+    pub connection: Pubkey,
+    pub user: Pubkey,
+    pub memo_reward: u64,
+    pub stake_returned: u64,        // ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionCompleted {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub agent: Pubkey,
+    pub agent_reward: u64,
+    pub pair_connections_completed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConnectionExpired {
+    pub connection: Pubkey,
+    pub user_a: Pubkey,
+    pub user_b: Pubkey,
+    pub stake_returned_a: u64,      // ME base units
+    pub stake_returned_b: u64,      // ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct NoShowSettled {
+    pub connection: Pubkey,
+    pub present_user: Pubkey,
+    pub absent_user: Pubkey,
+    pub forfeited: u64,             // ME base units
+    pub paid_to_present: u64,       // ME base units
+    pub burned: u64,                // ME base units
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PinAttemptFailed {
+    pub connection: Pubkey,
+    pub user: Pubkey,
+    pub failed_attempts: u8,
+    pub max_attempts: u8,
+    pub locked_until: i64,
+    pub timestamp: i64,
+}
+
+// ============================================================================
+// Instruction Contexts
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct InitializeGlobal<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 32 + 32 + AdminMultisig::SPACE + 32 + 4 + 8 + 8 + ProgramConfig::SPACE,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = TOKEN_DECIMALS,
+        mint::authority = global_state,
+        seeds = [b"memo_mint"],
+        bump
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump,
+        constraint = global_state.pending_admin == new_admin.key() @ ErrorCode::NotPendingAdmin
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAdminMultisig<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(agent: Pubkey)]
+pub struct RegisterAgent<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [b"agent", agent.as_ref()],
+        bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAgent<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent", agent_account.agent.as_ref()],
+        bump = agent_account.bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BondAgent<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent", agent.key().as_ref()],
+        bump = agent_account.bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    /// MEMO bond vault for this agent, owned by the global state
+    #[account(
+        init_if_needed,
+        payer = agent,
+        token::mint = memo_mint,
+        token::authority = global_state,
+        seeds = [b"agent_bond", agent.key().as_ref()],
+        bump
+    )]
+    pub agent_bond_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = memo_mint,
+        token::authority = agent
+    )]
+    pub agent_memo_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_AGENT_BOND) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        address = global_state.memo_mint
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub agent: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RequestUnbond<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent", agent.key().as_ref()],
+        bump = agent_account.bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub agent: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawUnbonded<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent", agent.key().as_ref()],
+        bump = agent_account.bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"agent_bond", agent.key().as_ref()],
+        bump
+    )]
+    pub agent_bond_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = global_state.memo_mint,
+        token::authority = agent
+    )]
+    pub agent_memo_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_AGENT_BOND) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub agent: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SlashAgent<'info> {
+    #[account(
+        mut,
+        seeds = [b"agent", agent_account.agent.as_ref()],
+        bump = agent_account.bump
+    )]
+    pub agent_account: Account<'info, AgentAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"agent_bond", agent_account.agent.as_ref()],
+        bump
+    )]
+    pub agent_bond_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        address = global_state.memo_mint
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Checked against the admin key or multisig in the handler
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    /// Admin or guardian, checked in the handler
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_id: String, user_id_hash: [u8; 32])]
+pub struct InitializeUser<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 64 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [b"user", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        mint::decimals = TOKEN_DECIMALS,
+        mint::authority = me_mint,
+        seeds = [b"me_mint", user_id_hash.as_ref()],
+        bump
+    )]
+    pub me_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = me_mint,
+        token::authority = authority,
+        seeds = [b"user_me_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_me_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = memo_mint,
+        token::authority = authority,
+        seeds = [b"user_memo_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_memo_ata: Account<'info, TokenAccount>,
+
+    /// Escrow vault for this user's locked ME, owned by the global state
+    #[account(
+        init,
+        payer = payer,
+        token::mint = me_mint,
+        token::authority = global_state,
+        seeds = [b"me_escrow", me_mint.key().as_ref()],
+        bump
+    )]
+    pub me_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_INITIALIZE_USER) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        address = global_state.memo_mint
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    /// CHECK: Wallet that will control the user and own its token accounts.
+    /// May be a custodial agent key or the user's own wallet.
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_id_hash: [u8; 32])]
+pub struct ClaimMe<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", user_id_hash.as_ref()],
+        bump = user_account.bump,
+        has_one = authority @ ErrorCode::UnauthorizedAuthority,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"me_mint", user_id_hash.as_ref()],
+        bump
+    )]
+    pub me_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_me_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_CLAIM_ME) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_id_hash: [u8; 32])]
+pub struct LockMeForMemo<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", user_id_hash.as_ref()],
+        bump = user_account.bump,
+        has_one = authority @ ErrorCode::UnauthorizedAuthority,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_me_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_memo_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_memo_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_LOCK_ME_FOR_MEMO) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        address = global_state.memo_mint
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"me_escrow", me_mint.key().as_ref()],
+        bump
+    )]
+    pub me_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        address = user_account.me_mint
+    )]
+    pub me_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [b"lock_position", user_account.key().as_ref(), &user_account.lock_positions_opened.to_le_bytes()],
+        bump
+    )]
+    pub lock_position: Account<'info, LockPosition>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_id_hash: [u8; 32])]
+pub struct WithdrawLockedMe<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", user_id_hash.as_ref()],
+        bump = user_account.bump,
+        has_one = authority @ ErrorCode::UnauthorizedAuthority,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"lock_position", user_account.key().as_ref(), &lock_position.position_id.to_le_bytes()],
+        bump = lock_position.bump,
+        constraint = lock_position.user == user_account.key() @ ErrorCode::UnauthorizedUser
+    )]
+    pub lock_position: Account<'info, LockPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_me_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_memo_token", user_id_hash.as_ref()],
+        bump
+    )]
+    pub user_memo_ata: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_WITHDRAW_LOCKED_ME) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        address = global_state.memo_mint
+    )]
+    pub memo_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"me_escrow", me_mint.key().as_ref()],
+        bump
+    )]
+    pub me_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = user_account.me_mint
+    )]
+    pub me_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(connection_id: String)]
+pub struct CreateConnection<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 64 + 32 + 32 + 32 + 32 + 1 + 1 + 8 + 8 + 1 + 8 + 1 + 1 + 8 + 8 + 32 + 1 + 32 + 8 + 8 + 1,
+        seeds = [b"connection", connection_id.as_bytes()],
+        bump
+    )]
+    pub connection_account: Account<'info, ConnectionAccount>,
+
+    #[account(
+        mut,
+        constraint = user_a_account.key() != user_b_account.key() @ ErrorCode::SameUserConnection
+    )]
+    pub user_a_account: Account<'info, UserAccount>,
+
+    pub user_b_account: Account<'info, UserAccount>,
+
+    /// Shared by every connection between these two users, whichever side is A
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1,
+        seeds = [
+            b"pair",
+            user_a_account.key().min(user_b_account.key()).as_ref(),
+            user_a_account.key().max(user_b_account.key()).as_ref()
+        ],
+        bump
+    )]
+    pub pair_account: Box<Account<'info, PairAccount>>,
+
+    #[account(
+        address = user_a_account.me_mint
+    )]
+    pub user_a_me_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        address = user_b_account.me_mint
+    )]
+    pub user_b_me_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = user_a_me_mint,
+        token::authority = global_state,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_a_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_a: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = user_b_me_mint,
+        token::authority = global_state,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_b_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_b: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_CREATE_CONNECTION) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        address = user_a_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
+    pub user_a_authority: Signer<'info>,
+
+    /// Registered agent brokering the match, recorded to receive the completion reward
+    pub agent: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"agent", agent.key().as_ref()],
+        bump = agent_account.bump,
+        constraint = agent_account.active @ ErrorCode::AgentNotActive,
+        constraint = agent_account.bonded_amount >= global_state.config.min_agent_bond @ ErrorCode::InsufficientAgentBond
+    )]
+    pub agent_account: Box<Account<'info, AgentAccount>>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_b_id_hash: [u8; 32])]
+pub struct AcceptConnection<'info> {
+    #[account(
+        mut,
+        constraint = connection_account.state == ConnectionState::Proposed @ ErrorCode::InvalidConnectionState
+    )]
+    pub connection_account: Box<Account<'info, ConnectionAccount>>,
+
+    #[account(
+        address = connection_account.user_a @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_a_account: Box<Account<'info, UserAccount>>,
+
+    #[account(
+        seeds = [b"user", user_b_id_hash.as_ref()],
+        bump = user_b_account.bump,
+        address = connection_account.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_account: Box<Account<'info, UserAccount>>,
+
+    #[account(
+        mut,
+        token::mint = user_a_account.me_mint,
+        token::authority = user_a_authority
+    )]
+    pub user_a_me_ata: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_b_id_hash.as_ref()],
+        bump
+    )]
+    pub user_b_me_ata: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_a_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_a: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_b_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_b: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_RESPOND_TO_CONNECTION) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        address = user_a_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
+    pub user_a_authority: Signer<'info>,
+
+    #[account(
+        address = user_b_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
+    pub user_b_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(user_b_id_hash: [u8; 32])]
+pub struct DeclineConnection<'info> {
+    #[account(
+        mut,
+        constraint = connection_account.state == ConnectionState::Proposed @ ErrorCode::InvalidConnectionState
+    )]
+    pub connection_account: Box<Account<'info, ConnectionAccount>>,
+
+    #[account(
+        seeds = [b"user", user_b_id_hash.as_ref()],
+        bump = user_b_account.bump,
+        address = connection_account.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_account: Box<Account<'info, UserAccount>>,
+
+    /// Released from its open connection
+    #[account(
+        mut,
+        address = connection_account.pair
+    )]
+    pub pair_account: Box<Account<'info, PairAccount>>,
 
-#[derive(Accounts)]
-pub struct InitializeGlobal<'info> {
     #[account(
-        init,
-        payer = admin,
-        space = 8 + 32 + 32 + 32 + 8 + 8,
         seeds = [b"global_state"],
-        bump
+        bump,
+        constraint = !global_state.is_paused(PAUSE_RESPOND_TO_CONNECTION) @ ErrorCode::InstructionPaused
     )]
     pub global_state: Account<'info, GlobalState>,
 
     #[account(
-        init,
-        payer = admin,
-        mint::decimals = TOKEN_DECIMALS,
-        mint::authority = global_state,
-        seeds = [b"memo_mint"],
-        bump
+        address = user_b_account.authority @ ErrorCode::UnauthorizedAuthority
     )]
-    pub memo_mint: Account<'info, Mint>,
+    pub user_b_authority: Signer<'info>,
+}
 
+#[derive(Accounts)]
+#[instruction(user_a_id_hash: [u8; 32], user_b_id_hash: [u8; 32], canceller_id_hash: [u8; 32])]
+pub struct CancelConnection<'info> {
     #[account(
-        init,
-        payer = admin,
-        token::mint = memo_mint,
-        token::authority = global_state,
-        seeds = [b"me_escrow"],
-        bump
+        mut,
+        constraint = matches!(
+            connection_account.state,
+            ConnectionState::Proposed | ConnectionState::Accepted | ConnectionState::Scheduled
+        ) @ ErrorCode::InvalidConnectionState
     )]
-    pub me_escrow: Account<'info, TokenAccount>,
+    pub connection_account: Box<Account<'info, ConnectionAccount>>,
 
-    #[account(mut)]
-    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"user", user_a_id_hash.as_ref()],
+        bump = user_a_account.bump,
+        address = connection_account.user_a @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_a_account: Box<Account<'info, UserAccount>>,
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    #[account(
+        seeds = [b"user", user_b_id_hash.as_ref()],
+        bump = user_b_account.bump,
+        address = connection_account.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub user_b_account: Box<Account<'info, UserAccount>>,
 
-#[derive(Accounts)]
-#[instruction(user_id: String, user_id_hash: [u8; 32])]
-pub struct InitializeUser<'info> {
     #[account(
-        init,
-        payer = payer,
-        space = 8 + 64 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
-        seeds = [b"user", user_id_hash.as_ref()],
+        mut,
+        address = user_a_account.me_mint
+    )]
+    pub user_a_me_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        address = user_b_account.me_mint
+    )]
+    pub user_b_me_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_a_id_hash.as_ref()],
         bump
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub user_a_me_ata: Box<Account<'info, TokenAccount>>,
 
     #[account(
-        init,
-        payer = payer,
-        mint::decimals = TOKEN_DECIMALS,
-        mint::authority = me_mint,
-        seeds = [b"me_mint", user_id_hash.as_ref()],
+        mut,
+        seeds = [b"user_me_token", user_b_id_hash.as_ref()],
         bump
     )]
-    pub me_mint: Account<'info, Mint>,
+    pub user_b_me_ata: Box<Account<'info, TokenAccount>>,
 
     #[account(
-        init,
-        payer = payer,
-        token::mint = me_mint,
-        token::authority = payer,
-        seeds = [b"user_me_token", user_id_hash.as_ref()],
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_a_account.key().as_ref()],
         bump
     )]
-    pub user_me_ata: Account<'info, TokenAccount>,
+    pub stake_vault_a: Box<Account<'info, TokenAccount>>,
 
     #[account(
-        init,
-        payer = payer,
-        token::mint = memo_mint,
-        token::authority = payer,
-        seeds = [b"user_memo_token", user_id_hash.as_ref()],
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_b_account.key().as_ref()],
         bump
     )]
-    pub user_memo_ata: Account<'info, TokenAccount>,
+    pub stake_vault_b: Box<Account<'info, TokenAccount>>,
+
+    /// Released from its open connection on cancel
+    #[account(
+        mut,
+        address = connection_account.pair
+    )]
+    pub pair_account: Box<Account<'info, PairAccount>>,
 
     #[account(
         seeds = [b"global_state"],
-        bump
+        bump,
+        constraint = !global_state.is_paused(PAUSE_CANCEL_CONNECTION) @ ErrorCode::InstructionPaused
     )]
     pub global_state: Account<'info, GlobalState>,
 
+    /// Authority of the cancelling user
     #[account(
-        address = global_state.memo_mint
+        constraint = (canceller_id_hash == user_a_id_hash && authority.key() == user_a_account.authority)
+            || (canceller_id_hash == user_b_id_hash && authority.key() == user_b_account.authority)
+            @ ErrorCode::UnauthorizedAuthority
     )]
-    pub memo_mint: Account<'info, Mint>,
-
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(user_id: String, user_id_hash: [u8; 32])]
-pub struct MintDailyMe<'info> {
+#[instruction(user_a_id_hash: [u8; 32], user_b_id_hash: [u8; 32])]
+pub struct RescheduleConnection<'info> {
     #[account(
         mut,
-        seeds = [b"user", user_id_hash.as_ref()],
-        bump = user_account.bump,
+        constraint = matches!(
+            connection_account.state,
+            ConnectionState::Accepted | ConnectionState::Scheduled
+        ) @ ErrorCode::InvalidConnectionState
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub connection_account: Account<'info, ConnectionAccount>,
 
     #[account(
-        mut,
-        seeds = [b"me_mint", user_id_hash.as_ref()],
-        bump
+        seeds = [b"user", user_a_id_hash.as_ref()],
+        bump = user_a_account.bump,
+        address = connection_account.user_a @ ErrorCode::UnauthorizedUser
     )]
-    pub me_mint: Account<'info, Mint>,
+    pub user_a_account: Account<'info, UserAccount>,
 
     #[account(
-        mut,
-        seeds = [b"user_me_token", user_id_hash.as_ref()],
-        bump
+        seeds = [b"user", user_b_id_hash.as_ref()],
+        bump = user_b_account.bump,
+        address = connection_account.user_b @ ErrorCode::UnauthorizedUser
     )]
-    pub user_me_ata: Account<'info, TokenAccount>,
+    pub user_b_account: Account<'info, UserAccount>,
 
-    pub payer: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_RESCHEDULE_CONNECTION) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        address = user_a_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
+    pub user_a_authority: Signer<'info>,
+
+    #[account(
+        address = user_b_account.authority @ ErrorCode::UnauthorizedAuthority
+    )]
+    pub user_b_authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
 #[instruction(user_id_hash: [u8; 32])]
-pub struct LockMeForMemo<'info> {
+pub struct UnlockConnection<'info> {
+    #[account(
+        mut,
+        constraint = matches!(
+            connection_account.state,
+            ConnectionState::Accepted | ConnectionState::Scheduled | ConnectionState::PartiallyUnlocked
+        ) @ ErrorCode::InvalidConnectionState
+    )]
+    pub connection_account: Account<'info, ConnectionAccount>,
+
     #[account(
         mut,
         seeds = [b"user", user_id_hash.as_ref()],
         bump = user_account.bump,
+        has_one = authority @ ErrorCode::UnauthorizedAuthority,
+        constraint = user_account.key() == connection_account.user_a || user_account.key() == connection_account.user_b @ ErrorCode::UnauthorizedUser
     )]
     pub user_account: Account<'info, UserAccount>,
 
@@ -503,9 +2902,17 @@ pub struct LockMeForMemo<'info> {
     pub user_memo_ata: Account<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"global_state"],
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_account.key().as_ref()],
         bump
     )]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_UNLOCK_CONNECTION) @ ErrorCode::InstructionPaused
+    )]
     pub global_state: Account<'info, GlobalState>,
 
     #[account(
@@ -514,90 +2921,161 @@ pub struct LockMeForMemo<'info> {
     )]
     pub memo_mint: Account<'info, Mint>,
 
+    /// MEMO account of the connection's agent, paid on the second unlock
     #[account(
         mut,
-        address = global_state.me_escrow
+        token::mint = memo_mint,
+        constraint = agent_memo_ata.owner == connection_account.agent @ ErrorCode::InvalidRecipient
     )]
-    pub me_escrow: Account<'info, TokenAccount>,
+    pub agent_memo_ata: Account<'info, TokenAccount>,
 
     #[account(
-        address = user_account.me_mint
+        mut,
+        seeds = [b"agent", connection_account.agent.as_ref()],
+        bump = agent_account.bump
     )]
-    pub me_mint: Account<'info, Mint>,
+    pub agent_account: Account<'info, AgentAccount>,
 
-    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        address = connection_account.pair
+    )]
+    pub pair_account: Account<'info, PairAccount>,
+
+    pub authority: Signer<'info>,
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(connection_id: String)]
-pub struct CreateConnection<'info> {
+#[instruction(user_a_id_hash: [u8; 32], user_b_id_hash: [u8; 32])]
+pub struct ExpireConnection<'info> {
     #[account(
-        init,
-        payer = payer,
-        space = 8 + 64 + 32 + 32 + 32 + 32 + 1 + 1 + 8 + 1,
-        seeds = [b"connection", connection_id.as_bytes()],
-        bump
+        mut,
+        constraint = matches!(
+            connection_account.state,
+            ConnectionState::Proposed | ConnectionState::Accepted | ConnectionState::Scheduled
+        ) @ ErrorCode::InvalidConnectionState
     )]
     pub connection_account: Account<'info, ConnectionAccount>,
 
     #[account(
-        mut,
-        constraint = user_a_account.key() != user_b_account.key() @ ErrorCode::SameUserConnection
+        seeds = [b"user", user_a_id_hash.as_ref()],
+        bump = user_a_account.bump,
+        address = connection_account.user_a @ ErrorCode::UnauthorizedUser
     )]
     pub user_a_account: Account<'info, UserAccount>,
 
-    #[account(mut)]
+    #[account(
+        seeds = [b"user", user_b_id_hash.as_ref()],
+        bump = user_b_account.bump,
+        address = connection_account.user_b @ ErrorCode::UnauthorizedUser
+    )]
     pub user_b_account: Account<'info, UserAccount>,
 
     #[account(
         mut,
-        seeds = [b"global_state"],
+        seeds = [b"user_me_token", user_a_id_hash.as_ref()],
         bump
     )]
-    pub global_state: Account<'info, GlobalState>,
+    pub user_a_me_ata: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"user_me_token", user_b_id_hash.as_ref()],
+        bump
+    )]
+    pub user_b_me_ata: Account<'info, TokenAccount>,
 
-    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_a_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"stake_vault", connection_account.key().as_ref(), user_b_account.key().as_ref()],
+        bump
+    )]
+    pub stake_vault_b: Account<'info, TokenAccount>,
+
+    /// Released from its open connection on expiry
+    #[account(
+        mut,
+        address = connection_account.pair
+    )]
+    pub pair_account: Account<'info, PairAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_EXPIRE_CONNECTION) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(user_id_hash: [u8; 32])]
-pub struct UnlockConnection<'info> {
+#[instruction(present_user_id_hash: [u8; 32], absent_user_id_hash: [u8; 32])]
+pub struct SettleNoShow<'info> {
     #[account(
         mut,
-        constraint = !connection_account.user_a_unlocked || !connection_account.user_b_unlocked @ ErrorCode::ConnectionFullyUnlocked
+        constraint = connection_account.state == ConnectionState::PartiallyUnlocked @ ErrorCode::NotOneSidedUnlock
     )]
     pub connection_account: Account<'info, ConnectionAccount>,
 
+    #[account(
+        seeds = [b"user", present_user_id_hash.as_ref()],
+        bump = present_user_account.bump,
+        constraint = present_user_account.key() == connection_account.user_a || present_user_account.key() == connection_account.user_b @ ErrorCode::UnauthorizedUser
+    )]
+    pub present_user_account: Account<'info, UserAccount>,
+
+    #[account(
+        seeds = [b"user", absent_user_id_hash.as_ref()],
+        bump = absent_user_account.bump,
+        constraint = absent_user_account.key() == connection_account.user_a || absent_user_account.key() == connection_account.user_b @ ErrorCode::UnauthorizedUser,
+        constraint = absent_user_account.key() != present_user_account.key() @ ErrorCode::SameUserConnection
+    )]
+    pub absent_user_account: Account<'info, UserAccount>,
+
     #[account(
         mut,
-        constraint = user_account.key() == connection_account.user_a || user_account.key() == connection_account.user_b @ ErrorCode::UnauthorizedUser
+        address = absent_user_account.me_mint
     )]
-    pub user_account: Account<'info, UserAccount>,
+    pub absent_me_mint: Account<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [b"user_memo_token", user_id_hash.as_ref()],
+        seeds = [b"stake_vault", connection_account.key().as_ref(), absent_user_account.key().as_ref()],
         bump
     )]
-    pub user_memo_ata: Account<'info, TokenAccount>,
+    pub absent_stake_vault: Account<'info, TokenAccount>,
 
+    /// Present user's token account for the absent user's ME mint
     #[account(
-        seeds = [b"global_state"],
-        bump
+        mut,
+        token::mint = absent_me_mint,
+        constraint = forfeit_recipient.owner == present_user_account.authority @ ErrorCode::InvalidRecipient
     )]
-    pub global_state: Account<'info, GlobalState>,
+    pub forfeit_recipient: Account<'info, TokenAccount>,
 
+    /// Released from its open connection on settlement
     #[account(
         mut,
-        address = global_state.memo_mint
+        address = connection_account.pair
     )]
-    pub memo_mint: Account<'info, Mint>,
+    pub pair_account: Account<'info, PairAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = !global_state.is_paused(PAUSE_SETTLE_NO_SHOW) @ ErrorCode::InstructionPaused
+    )]
+    pub global_state: Account<'info, GlobalState>,
 
-    pub payer: Signer<'info>,
     pub token_program: Program<'info, Token>,
 }
 
@@ -605,9 +3083,12 @@ pub struct UnlockConnection<'info> {
 // Error Codes
 // ============================================================================
 
+// Error codes are numbered by position, so variants that are no longer
+// returned stay in place to keep the codes clients already match on
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Daily minting limit of 24 ME reached. Try again tomorrow.")]
+    // Unused since claim_me replaced daily minting, kept for error-code stability
+    #[msg("Daily minting limit reached. Try again tomorrow.")]
     DailyLimitReached,
 
     #[msg("User ID too long (max 64 bytes)")]
@@ -616,6 +3097,7 @@ pub enum ErrorCode {
     #[msg("Invalid amount - must be greater than 0")]
     InvalidAmount,
 
+    // Unused since wrong PINs are recorded instead, kept for error-code stability
     #[msg("Invalid PIN")]
     InvalidPin,
 
@@ -625,9 +3107,222 @@ pub enum ErrorCode {
     #[msg("Already unlocked")]
     AlreadyUnlocked,
 
+    // Unused since ConnectionState checks replaced it, kept for error-code stability
     #[msg("Connection already fully unlocked")]
     ConnectionFullyUnlocked,
 
     #[msg("Cannot create connection with same user")]
     SameUserConnection,
+
+    #[msg("Expiry window out of range (1 hour to 30 days)")]
+    InvalidExpiry,
+
+    #[msg("Connection has expired")]
+    ConnectionExpired,
+
+    #[msg("Connection has not reached its expiry deadline")]
+    ConnectionNotExpired,
+
+    #[msg("Stake amount out of range")]
+    InvalidStakeAmount,
+
+    // Unused since ConnectionState checks replaced it, kept for error-code stability
+    #[msg("Connection was partially unlocked - settle the no-show instead")]
+    ConnectionPartiallyUnlocked,
+
+    #[msg("Exactly one user must have unlocked the connection")]
+    NotOneSidedUnlock,
+
+    #[msg("User has not unlocked this connection")]
+    NotUnlocked,
+
+    #[msg("Recipient token account is not owned by the user")]
+    InvalidRecipient,
+
+    #[msg("Too many wrong PINs - try again after the lockout")]
+    PinLockedOut,
+
+    #[msg("Signer is not the authority of this user")]
+    UnauthorizedAuthority,
+
+    #[msg("User ID must not be empty")]
+    InvalidUserId,
+
+    #[msg("User ID hash does not match the user ID")]
+    InvalidUserIdHash,
+
+    #[msg("Escrow vault balance does not cover the user's locked ME")]
+    EscrowBalanceMismatch,
+
+    #[msg("Lock duration does not match a configured lock tier")]
+    InvalidLockDuration,
+
+    #[msg("Config value out of bounds")]
+    InvalidConfig,
+
+    #[msg("Signer is not the program admin")]
+    UnauthorizedAdmin,
+
+    #[msg("Signer is not the pending admin")]
+    NotPendingAdmin,
+
+    #[msg("Invalid admin multisig signers or threshold")]
+    InvalidMultisig,
+
+    #[msg("This instruction is paused")]
+    InstructionPaused,
+
+    #[msg("No pause flags given")]
+    InvalidPauseFlags,
+
+    #[msg("Agent is not registered or has been revoked")]
+    AgentNotActive,
+
+    #[msg("Agent bond is too small for this operation")]
+    InsufficientAgentBond,
+
+    #[msg("Unbonded MEMO is still in its cooldown")]
+    UnbondCooldownActive,
+
+    #[msg("These users already have an open connection or had one too recently")]
+    PairCooldownActive,
+
+    #[msg("Connection is not in a state that allows this instruction")]
+    InvalidConnectionState,
+
+    #[msg("Connection state transition not allowed")]
+    InvalidStateTransition,
+
+    #[msg("Meeting window must be in the future, non-empty and within 30 days")]
+    InvalidMeetingWindow,
+
+    #[msg("No ME has accrued since the last claim")]
+    NothingToClaim,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(config: ProgramConfig) -> bool {
+        config.validate() == Err(ErrorCode::InvalidConfig.into())
+    }
+
+    fn connection() -> ConnectionAccount {
+        ConnectionAccount {
+            connection_id: [0; 64],
+            user_a: Pubkey::new_unique(),
+            user_b: Pubkey::new_unique(),
+            pin_a_hash: [0; 32],
+            pin_b_hash: [0; 32],
+            user_a_unlocked: false,
+            user_b_unlocked: false,
+            created_at: 0,
+            expires_at: 0,
+            state: ConnectionState::Accepted,
+            stake_amount: 0,
+            user_a_failed_attempts: 0,
+            user_b_failed_attempts: 0,
+            user_a_locked_until: 0,
+            user_b_locked_until: 0,
+            agent: Pubkey::new_unique(),
+            agent_rewarded: false,
+            pair: Pubkey::new_unique(),
+            meeting_starts_at: 0,
+            meeting_ends_at: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(ProgramConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_config() {
+        let default = ProgramConfig::default();
+        assert!(invalid(ProgramConfig { daily_me_limit: 0, ..default }));
+        assert!(invalid(ProgramConfig { day_in_seconds: MIN_DAY_IN_SECONDS - 1, ..default }));
+        assert!(invalid(ProgramConfig { me_carry_over_days: MAX_ME_CARRY_OVER_DAYS + 1, ..default }));
+        assert!(invalid(ProgramConfig { pair_cooldown: -1, ..default }));
+        assert!(invalid(ProgramConfig { cancel_penalty_bps: BPS_DENOMINATOR + 1, ..default }));
+        assert!(invalid(ProgramConfig { no_show_forfeit_bps: BPS_DENOMINATOR + 1, ..default }));
+        assert!(invalid(ProgramConfig { max_pin_attempts: 0, ..default }));
+        assert!(invalid(ProgramConfig { pin_lockout_seconds: MIN_PIN_LOCKOUT_SECONDS - 1, ..default }));
+
+        let mut tiers = default.lock_tiers;
+        tiers.swap(0, 1);
+        assert!(invalid(ProgramConfig { lock_tiers: tiers, ..default }));
+    }
+
+    #[test]
+    fn pair_reward_decays_per_previous_meeting() {
+        let config = ProgramConfig::default();
+        let rewards: Vec<u64> = (0..6).map(|n| config.pair_reward(8, n)).collect();
+        assert_eq!(rewards, [8, 4, 2, 1, 0, 0]);
+
+        let no_decay = ProgramConfig { pair_reward_decay_bps: BPS_DENOMINATOR, ..config };
+        assert_eq!(no_decay.pair_reward(8, 100), 8);
+    }
+
+    #[test]
+    fn accrued_me_pays_whole_tokens_and_rounds_paid_time_up() {
+        let config = ProgramConfig::default();
+        assert_eq!(config.accrued_me(-10), (0, 0));
+        assert_eq!(config.accrued_me(3599), (0, 0));
+        assert_eq!(config.accrued_me(3601), (1, 3600));
+        assert_eq!(config.accrued_me(86400), (24, 86400));
+
+        // 86400 / 7 is not whole, so the paid time rounds up to never pay ahead
+        let config = ProgramConfig { daily_me_limit: 7, ..config };
+        assert_eq!(config.accrued_me(12343), (1, 12343));
+    }
+
+    #[test]
+    fn accrued_me_caps_at_carry_over_and_forfeits_the_rest() {
+        let config = ProgramConfig::default();
+        assert_eq!(config.accrued_me(3 * 86400), (72, 3 * 86400));
+        assert_eq!(config.accrued_me(10 * 86400), (72, 10 * 86400));
+    }
+
+    #[test]
+    fn connection_state_transitions() {
+        use ConnectionState::*;
+        assert!(Proposed.can_transition_to(Accepted));
+        assert!(Accepted.can_transition_to(Scheduled));
+        assert!(Scheduled.can_transition_to(Scheduled));
+        assert!(PartiallyUnlocked.can_transition_to(Completed));
+
+        assert!(!Proposed.can_transition_to(PartiallyUnlocked));
+        assert!(!PartiallyUnlocked.can_transition_to(Cancelled));
+        for next in [Proposed, Accepted, Scheduled, PartiallyUnlocked, Completed, Expired, Cancelled, Disputed] {
+            assert!(!Completed.can_transition_to(next));
+            assert!(!Expired.can_transition_to(next));
+            assert!(!Cancelled.can_transition_to(next));
+            assert!(!Disputed.can_transition_to(next));
+            assert!(!next.can_transition_to(Disputed));
+        }
+    }
+
+    #[test]
+    fn record_failed_pin_locks_out_after_max_attempts() {
+        let config = ProgramConfig::default();
+        let mut connection = connection();
+        let now = 1_000;
+
+        for attempt in 1..config.max_pin_attempts {
+            assert_eq!(connection.record_failed_pin(true, now, &config), (attempt, 0));
+        }
+        assert_eq!(
+            connection.record_failed_pin(true, now, &config),
+            (config.max_pin_attempts, now + config.pin_lockout_seconds)
+        );
+
+        // The counter starts over for the next round, and B is unaffected
+        assert_eq!(connection.user_a_failed_attempts, 0);
+        assert_eq!(connection.user_b_failed_attempts, 0);
+        assert_eq!(connection.user_b_locked_until, 0);
+        assert_eq!(connection.record_failed_pin(false, now, &config), (1, 0));
+    }
 }
@@ -31,15 +31,9 @@ async function main() {
     PROGRAM_ID
   );
 
-  const [meEscrow] = PublicKey.findProgramAddressSync(
-    [Buffer.from('me_escrow')],
-    PROGRAM_ID
-  );
-
   console.log('\nPDAs:');
   console.log('  Global State:', globalState.toString());
   console.log('  MEMO Mint:', memoMint.toString());
-  console.log('  ME Escrow:', meEscrow.toString());
 
   // Check if already initialized
   try {
@@ -66,7 +60,6 @@ async function main() {
     const keys = [
       { pubkey: globalState, isSigner: false, isWritable: true },
       { pubkey: memoMint, isSigner: false, isWritable: true },
-      { pubkey: meEscrow, isSigner: false, isWritable: true },
       { pubkey: adminKeypair.publicKey, isSigner: true, isWritable: true },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
@@ -99,7 +92,6 @@ async function main() {
     console.log('  Explorer: https://explorer.solana.com/tx/' + tx + '?cluster=devnet');
     console.log('\nGlobal State Account:', globalState.toString());
     console.log('MEMO Mint:', memoMint.toString());
-    console.log('ME Escrow:', meEscrow.toString());
   } catch (error: any) {
     console.error('\n✗ Error initializing global state:', error?.message || String(error));
     if (error?.logs) {
@@ -10,10 +10,11 @@ import {
 import {
   TOKEN_PROGRAM_ID,
   ASSOCIATED_TOKEN_PROGRAM_ID,
-  getAssociatedTokenAddress,
-  getAccount
+  getAccount,
+  getMint,
+  createAccount
 } from "@solana/spl-token";
-import { createHash } from "crypto";
+import { createHash, randomBytes } from "crypto";
 import { assert } from "chai";
 
 describe("unified-token-program", () => {
@@ -30,9 +31,9 @@ describe("unified-token-program", () => {
   const userAId = "telegram:alice123";
   const userBId = "telegram:bob456";
 
-  // Helper function: Hash user ID
+  // Helper function: Hash user ID under the program's domain prefix
   function hashUserId(userId: string): Buffer {
-    return createHash('sha256').update(userId).digest();
+    return createHash('sha256').update("withparen:user_id:v1:").update(userId).digest();
   }
 
   // Helper function: Derive user PDA
@@ -53,15 +54,160 @@ describe("unified-token-program", () => {
     );
   }
 
-  // Helper function: Hash PIN
-  function hashPin(pin: string): Buffer {
-    return createHash('sha256').update(pin).digest();
+  // Helper function: Derive a user's ME or MEMO token account PDA
+  function deriveUserTokenPDA(kind: "user_me_token" | "user_memo_token", userId: string): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from(kind), hashUserId(userId)],
+      program.programId
+    );
+  }
+
+  // Helper function: Salted PIN commitment bound to the connection and PIN owner
+  function commitPin(salt: Buffer, connection: PublicKey, owner: PublicKey, pin: string): Buffer {
+    return createHash('sha256')
+      .update("withparen:pin:v1")
+      .update(salt)
+      .update(connection.toBuffer())
+      .update(owner.toBuffer())
+      .update(pin)
+      .digest();
   }
 
   // Global PDAs
   let globalState: PublicKey;
   let memoMint: PublicKey;
-  let meEscrow: PublicKey;
+
+  // Admin is every user's authority and brokers every match as the agent
+  const [agentAccount] = PublicKey.findProgramAddressSync(
+    [Buffer.from("agent"), admin.publicKey.toBuffer()],
+    program.programId
+  );
+
+  // Helper function: Derive the pair PDA of User A and the given counterparty
+  function derivePairPDA(otherId: string = userBId): [PublicKey, number] {
+    const [userAPDA] = deriveUserPDA(userAId);
+    const [userBPDA] = deriveUserPDA(otherId);
+    const [low, high] = [userAPDA, userBPDA].sort((x, y) => Buffer.compare(x.toBuffer(), y.toBuffer()));
+    return PublicKey.findProgramAddressSync(
+      [Buffer.from("pair"), low.toBuffer(), high.toBuffer()],
+      program.programId
+    );
+  }
+
+  // Helper function: Propose a connection from User A to User B (or another
+  // counterparty), returning its PDA
+  async function proposeConnection(
+    connectionId: string,
+    pins: { pinA: string; saltA: Buffer; pinB: string; saltB: Buffer },
+    expirySeconds: number,
+    stake: number,
+    otherId: string = userBId
+  ): Promise<PublicKey> {
+    const [userAPDA] = deriveUserPDA(userAId);
+    const [userBPDA] = deriveUserPDA(otherId);
+    const [connection] = PublicKey.findProgramAddressSync(
+      [Buffer.from("connection"), Buffer.from(connectionId)],
+      program.programId
+    );
+
+    await program.methods
+      .createConnection(
+        connectionId,
+        userAId,
+        otherId,
+        Array.from(commitPin(pins.saltA, connection, userAPDA, pins.pinA)),
+        Array.from(commitPin(pins.saltB, connection, userBPDA, pins.pinB)),
+        new BN(expirySeconds),
+        new BN(stake)
+      )
+      .accounts({
+        connectionAccount: connection,
+        userAAccount: userAPDA,
+        userBAccount: userBPDA,
+        userAMeMint: deriveMeMintPDA(userAId)[0],
+        userBMeMint: deriveMeMintPDA(otherId)[0],
+        globalState,
+        userAAuthority: admin.publicKey,
+        agent: admin.publicKey,
+        agentAccount,
+        pairAccount: derivePairPDA(otherId)[0],
+        payer: admin.publicKey,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+    return connection;
+  }
+
+  // Helper function: Accept a proposed connection as User B, or as another
+  // counterparty whose authority is the given keypair
+  async function acceptAsUserB(connection: PublicKey, otherId: string = userBId, authority?: Keypair) {
+    return program.methods
+      .acceptConnection(Array.from(hashUserId(otherId)))
+      .accounts({
+        connectionAccount: connection,
+        userAAccount: deriveUserPDA(userAId)[0],
+        userBAccount: deriveUserPDA(otherId)[0],
+        userAMeAta: deriveUserTokenPDA("user_me_token", userAId)[0],
+        userBMeAta: deriveUserTokenPDA("user_me_token", otherId)[0],
+        globalState,
+        userAAuthority: admin.publicKey,
+        userBAuthority: authority ? authority.publicKey : admin.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers(authority ? [authority] : [])
+      .rpc();
+  }
+
+  // Helper function: Agree a meeting window, `startsIn` and `endsIn` seconds from now
+  async function scheduleConnection(connection: PublicKey, startsIn: number, endsIn: number) {
+    const now = Math.floor(Date.now() / 1000);
+    return program.methods
+      .rescheduleConnection(
+        Array.from(hashUserId(userAId)),
+        Array.from(hashUserId(userBId)),
+        new BN(now + startsIn),
+        new BN(now + endsIn)
+      )
+      .accounts({
+        connectionAccount: connection,
+        userAAccount: deriveUserPDA(userAId)[0],
+        userBAccount: deriveUserPDA(userBId)[0],
+        globalState,
+        userAAuthority: admin.publicKey,
+        userBAuthority: admin.publicKey,
+      })
+      .rpc();
+  }
+
+  // Helper function: Submit a PIN and salt to a connection as the given user
+  async function unlockAs(connection: PublicKey, userId: string, pin: string, salt: Buffer) {
+    return program.methods
+      .unlockConnection(Array.from(hashUserId(userId)), Array.from(Buffer.from(pin)), Array.from(salt))
+      .accounts({
+        connectionAccount: connection,
+        userAccount: deriveUserPDA(userId)[0],
+        userMemoAta: deriveUserTokenPDA("user_memo_token", userId)[0],
+        globalState,
+        memoMint,
+        agentMemoAta: deriveUserTokenPDA("user_memo_token", userAId)[0],
+        agentAccount,
+        pairAccount: derivePairPDA()[0],
+        authority: admin.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+  }
+
+  // Helper function: Wait for on-chain deadlines to pass
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+  // Helper function: Token balance in whole tokens
+  async function balanceOf(tokenAccount: PublicKey): Promise<number> {
+    const account = await getAccount(provider.connection, tokenAccount);
+    return Number(account.amount) / 1e9;
+  }
 
   before("Derive global PDAs", async () => {
     [globalState] = PublicKey.findProgramAddressSync(
@@ -74,26 +220,19 @@ describe("unified-token-program", () => {
       program.programId
     );
 
-    [meEscrow] = PublicKey.findProgramAddressSync(
-      [Buffer.from("me_escrow")],
-      program.programId
-    );
-
     console.log("Program ID:", program.programId.toString());
     console.log("Global State:", globalState.toString());
     console.log("MEMO Mint:", memoMint.toString());
-    console.log("ME Escrow:", meEscrow.toString());
   });
 
   describe("1. Initialize Global State", () => {
-    it("Should initialize global state with MEMO mint and escrow", async () => {
+    it("Should initialize global state with MEMO mint", async () => {
       try {
         const tx = await program.methods
           .initializeGlobal()
           .accounts({
             globalState,
             memoMint,
-            meEscrow,
             admin: admin.publicKey,
             systemProgram: SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
@@ -106,10 +245,10 @@ describe("unified-token-program", () => {
         // Verify global state
         const globalStateAccount = await program.account.globalState.fetch(globalState);
         assert.equal(globalStateAccount.memoMint.toString(), memoMint.toString());
-        assert.equal(globalStateAccount.meEscrow.toString(), meEscrow.toString());
         assert.equal(globalStateAccount.admin.toString(), admin.publicKey.toString());
         assert.equal(globalStateAccount.totalUsers.toNumber(), 0);
         assert.equal(globalStateAccount.totalConnections.toNumber(), 0);
+        assert.equal(globalStateAccount.config.dailyMeLimit.toNumber(), 24);
 
         console.log("✓ Global state initialized successfully");
       } catch (error) {
@@ -131,15 +270,8 @@ describe("unified-token-program", () => {
       [userAPDA] = deriveUserPDA(userAId);
       [userAMeMint] = deriveMeMintPDA(userAId);
 
-      userAMeAta = await getAssociatedTokenAddress(
-        userAMeMint,
-        admin.publicKey
-      );
-
-      userAMemoAta = await getAssociatedTokenAddress(
-        memoMint,
-        admin.publicKey
-      );
+      [userAMeAta] = deriveUserTokenPDA("user_me_token", userAId);
+      [userAMemoAta] = deriveUserTokenPDA("user_memo_token", userAId);
 
       const tx = await program.methods
         .initializeUser(userAId, userIdHash)
@@ -150,6 +282,7 @@ describe("unified-token-program", () => {
           userMemoAta: userAMemoAta,
           globalState,
           memoMint,
+          authority: admin.publicKey,
           payer: admin.publicKey,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -164,7 +297,6 @@ describe("unified-token-program", () => {
       const userAccount = await program.account.userAccount.fetch(userAPDA);
       assert.equal(userAccount.meMint.toString(), userAMeMint.toString());
       assert.equal(userAccount.totalMeMinted.toNumber(), 48);
-      assert.equal(userAccount.dailyMintedToday.toNumber(), 48);
 
       // Verify ME token balance (48 with 9 decimals)
       const meTokenAccount = await getAccount(provider.connection, userAMeAta);
@@ -194,15 +326,8 @@ describe("unified-token-program", () => {
       [userBPDA] = deriveUserPDA(userBId);
       [userBMeMint] = deriveMeMintPDA(userBId);
 
-      userBMeAta = await getAssociatedTokenAddress(
-        userBMeMint,
-        admin.publicKey
-      );
-
-      userBMemoAta = await getAssociatedTokenAddress(
-        memoMint,
-        admin.publicKey
-      );
+      [userBMeAta] = deriveUserTokenPDA("user_me_token", userBId);
+      [userBMemoAta] = deriveUserTokenPDA("user_memo_token", userBId);
 
       const tx = await program.methods
         .initializeUser(userBId, userIdHash)
@@ -213,6 +338,7 @@ describe("unified-token-program", () => {
           userMemoAta: userBMemoAta,
           globalState,
           memoMint,
+          authority: admin.publicKey,
           payer: admin.publicKey,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -231,29 +357,30 @@ describe("unified-token-program", () => {
     });
   });
 
-  describe("4. Mint Daily ME Tokens", () => {
-    it("Should fail to mint more ME on same day (daily limit)", async () => {
+  describe("4. Claim Accrued ME", () => {
+    it("Should fail to claim before a whole ME has accrued", async () => {
       const userIdHash = Array.from(hashUserId(userAId));
       const [userAPDA] = deriveUserPDA(userAId);
       const [userAMeMint] = deriveMeMintPDA(userAId);
-      const userAMeAta = await getAssociatedTokenAddress(userAMeMint, admin.publicKey);
+      const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
 
       try {
         await program.methods
-          .mintDailyMe(userAId, userIdHash)
+          .claimMe(userIdHash)
           .accounts({
             userAccount: userAPDA,
             meMint: userAMeMint,
             userMeAta: userAMeAta,
-            payer: admin.publicKey,
+            globalState,
+            authority: admin.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
           })
           .rpc();
 
-        assert.fail("Should have failed due to daily limit");
+        assert.fail("Should have failed with nothing to claim");
       } catch (error) {
-        assert.include(error.toString(), "DailyLimitReached");
-        console.log("✓ Correctly rejected minting beyond daily limit");
+        assert.include(error.toString(), "NothingToClaim");
+        console.log("✓ Correctly rejected claiming before any ME accrued");
       }
     });
   });
@@ -263,8 +390,12 @@ describe("unified-token-program", () => {
       const userIdHash = Array.from(hashUserId(userAId));
       const [userAPDA] = deriveUserPDA(userAId);
       const [userAMeMint] = deriveMeMintPDA(userAId);
-      const userAMeAta = await getAssociatedTokenAddress(userAMeMint, admin.publicKey);
-      const userAMemoAta = await getAssociatedTokenAddress(memoMint, admin.publicKey);
+      const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
+      const userAMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
+      const [meEscrow] = PublicKey.findProgramAddressSync(
+        [Buffer.from("me_escrow"), userAMeMint.toBuffer()],
+        program.programId
+      );
 
       // Get balances before
       const meBeforeAccount = await getAccount(provider.connection, userAMeAta);
@@ -273,9 +404,15 @@ describe("unified-token-program", () => {
       const memoBeforeAccount = await getAccount(provider.connection, userAMemoAta);
       const memoBalanceBefore = Number(memoBeforeAccount.amount) / 1e9;
 
-      // Lock 10 ME for 10 MEMO
+      // First lock position for this user
+      const [lockPosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("lock_position"), userAPDA.toBuffer(), new BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      // Lock 10 ME for 7 days (1.0x) for 10 MEMO
       const tx = await program.methods
-        .lockMeForMemo(userIdHash, new BN(10))
+        .lockMeForMemo(userIdHash, new BN(10), 7)
         .accounts({
           userAccount: userAPDA,
           userMeAta: userAMeAta,
@@ -284,7 +421,9 @@ describe("unified-token-program", () => {
           memoMint,
           meEscrow,
           meMint: userAMeMint,
-          payer: admin.publicKey,
+          lockPosition,
+          authority: admin.publicKey,
+          systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
@@ -307,15 +446,46 @@ describe("unified-token-program", () => {
       assert.equal(userAccount.totalMeLocked.toNumber(), 10);
       assert.equal(userAccount.totalMemoEarned.toNumber(), 10);
 
+      // Verify the per-mint escrow vault holds the locked ME
+      const escrowAccount = await getAccount(provider.connection, meEscrow);
+      assert.equal(escrowAccount.amount.toString(), (10 * 1e9).toString());
+
+      // Verify the lock position
+      const position = await program.account.lockPosition.fetch(lockPosition);
+      assert.equal(position.amount.toNumber(), 10);
+      assert.equal(position.memoReward.toNumber(), 10);
+      assert.equal(position.unlocksAt.toNumber() - position.lockedAt.toNumber(), 7 * 86400);
+
       console.log("✓ Successfully locked 10 ME and received 10 MEMO");
     });
   });
 
   describe("6. Create Connection", () => {
     let connectionPDA: PublicKey;
-    const connectionId = `${userAId}-${userBId}`;
+    const connectionId = "alice123-bob456"; // PDA seed, at most 32 bytes
     const pinA = "1234";
     const pinB = "5678";
+    const saltA = randomBytes(32);
+    const saltB = randomBytes(32);
+
+    it("Should register admin as a matchmaker agent", async () => {
+      await program.methods
+        .registerAgent(admin.publicKey)
+        .accounts({
+          agentAccount,
+          globalState,
+          admin: admin.publicKey,
+          payer: admin.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.active, true);
+      assert.equal(agent.connectionsCreated.toNumber(), 0);
+
+      console.log("✓ Agent registered");
+    });
 
     it("Should create connection between User A and User B", async () => {
       const [userAPDA] = deriveUserPDA(userAId);
@@ -326,18 +496,28 @@ describe("unified-token-program", () => {
         program.programId
       );
 
-      const pinAHash = Array.from(hashPin(pinA));
-      const pinBHash = Array.from(hashPin(pinB));
+      const pinAHash = Array.from(commitPin(saltA, connectionPDA, userAPDA, pinA));
+      const pinBHash = Array.from(commitPin(saltB, connectionPDA, userBPDA, pinB));
+
+      const [userAMeMint] = deriveMeMintPDA(userAId);
+      const [userBMeMint] = deriveMeMintPDA(userBId);
 
       const tx = await program.methods
-        .createConnection(connectionId, userAId, userBId, pinAHash, pinBHash)
+        .createConnection(connectionId, userAId, userBId, pinAHash, pinBHash, new BN(7 * 86400), new BN(24))
         .accounts({
           connectionAccount: connectionPDA,
           userAAccount: userAPDA,
           userBAccount: userBPDA,
+          userAMeMint,
+          userBMeMint,
           globalState,
+          userAAuthority: admin.publicKey,
+          agent: admin.publicKey, // admin brokers the match in this test
+          agentAccount,
+          pairAccount: derivePairPDA()[0],
           payer: admin.publicKey,
           systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
@@ -349,6 +529,12 @@ describe("unified-token-program", () => {
       assert.equal(connection.userB.toString(), userBPDA.toString());
       assert.equal(connection.userAUnlocked, false);
       assert.equal(connection.userBUnlocked, false);
+      assert.deepEqual(connection.state, { proposed: {} });
+      assert.equal(connection.agent.toString(), admin.publicKey.toString());
+      assert.equal(connection.agentRewarded, false);
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.connectionsCreated.toNumber(), 1);
 
       // Verify global state updated
       const globalStateAccount = await program.account.globalState.fetch(globalState);
@@ -357,9 +543,104 @@ describe("unified-token-program", () => {
       console.log("✓ Connection created successfully");
     });
 
+    it("Should refuse a second connection for the pair while one is open", async () => {
+      try {
+        await proposeConnection("alice123-bob456-2", { pinA, saltA, pinB, saltB }, 7 * 86400, 1);
+        assert.fail("Should have failed - the pair already has an open connection");
+      } catch (error) {
+        assert.include(error.toString(), "PairCooldownActive");
+      }
+
+      const pair = await program.account.pairAccount.fetch(derivePairPDA()[0]);
+      assert.equal(pair.openConnections.toNumber(), 1);
+
+      console.log("✓ Parallel connection for the same pair rejected");
+    });
+
+    it("Should escrow both stakes when User B accepts the proposed connection", async () => {
+      const [userAPDA] = deriveUserPDA(userAId);
+      const [userBPDA] = deriveUserPDA(userBId);
+      const [stakeVaultA] = PublicKey.findProgramAddressSync(
+        [Buffer.from("stake_vault"), connectionPDA.toBuffer(), userAPDA.toBuffer()],
+        program.programId
+      );
+      const [stakeVaultB] = PublicKey.findProgramAddressSync(
+        [Buffer.from("stake_vault"), connectionPDA.toBuffer(), userBPDA.toBuffer()],
+        program.programId
+      );
+
+      // Proposing escrows nothing; both stakes move together on acceptance
+      assert.equal(await balanceOf(stakeVaultA), 0);
+      assert.equal(await balanceOf(stakeVaultB), 0);
+
+      await program.methods
+        .acceptConnection(Array.from(hashUserId(userBId)))
+        .accounts({
+          connectionAccount: connectionPDA,
+          userAAccount: userAPDA,
+          userBAccount: userBPDA,
+          userAMeAta: deriveUserTokenPDA("user_me_token", userAId)[0],
+          userBMeAta: deriveUserTokenPDA("user_me_token", userBId)[0],
+          globalState,
+          userAAuthority: admin.publicKey,
+          userBAuthority: admin.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const connection = await program.account.connectionAccount.fetch(connectionPDA);
+      assert.deepEqual(connection.state, { accepted: {} });
+      assert.equal(await balanceOf(stakeVaultA), 24);
+      assert.equal(await balanceOf(stakeVaultB), 24);
+
+      console.log("✓ Connection accepted by User B");
+    });
+
+    it("Should schedule a meeting window agreed by both users", async () => {
+      const [userAPDA] = deriveUserPDA(userAId);
+      const [userBPDA] = deriveUserPDA(userBId);
+      const now = Math.floor(Date.now() / 1000);
+      const startsAt = new BN(now + 3600);
+      const endsAt = new BN(now + 2 * 3600);
+
+      await program.methods
+        .rescheduleConnection(
+          Array.from(hashUserId(userAId)),
+          Array.from(hashUserId(userBId)),
+          startsAt,
+          endsAt
+        )
+        .accounts({
+          connectionAccount: connectionPDA,
+          userAAccount: userAPDA,
+          userBAccount: userBPDA,
+          globalState,
+          userAAuthority: admin.publicKey,
+          userBAuthority: admin.publicKey,
+        })
+        .rpc();
+
+      const connection = await program.account.connectionAccount.fetch(connectionPDA);
+      assert.deepEqual(connection.state, { scheduled: {} });
+      assert.equal(connection.expiresAt.toNumber(), endsAt.toNumber());
+
+      console.log("✓ Meeting window scheduled");
+    });
+
+    it("Should record a wrong PIN without failing the transaction", async () => {
+      await unlockAs(connectionPDA, userAId, "0000", saltB);
+
+      const connection = await program.account.connectionAccount.fetch(connectionPDA);
+      assert.equal(connection.userAUnlocked, false);
+      assert.equal(connection.userAFailedAttempts, 1);
+      assert.equal(connection.userALockedUntil.toNumber(), 0);
+
+      console.log("✓ Wrong PIN counted against User A");
+    });
+
     it("Should unlock connection with correct PIN and mint MEMO reward", async () => {
       const [userAPDA] = deriveUserPDA(userAId);
-      const userAMemoAta = await getAssociatedTokenAddress(memoMint, admin.publicKey);
+      const userAMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
 
       // Get MEMO balance before
       const memoBeforeAccount = await getAccount(provider.connection, userAMemoAta);
@@ -369,14 +650,17 @@ describe("unified-token-program", () => {
       const pinToSubmit = Buffer.from([0x35, 0x36, 0x37, 0x38]); // ASCII "5678"
 
       const tx = await program.methods
-        .unlockConnection(Array.from(pinToSubmit))
+        .unlockConnection(Array.from(hashUserId(userAId)), Array.from(pinToSubmit), Array.from(saltB))
         .accounts({
           connectionAccount: connectionPDA,
           userAccount: userAPDA,
           userMemoAta: userAMemoAta,
           globalState,
           memoMint,
-          payer: admin.publicKey,
+          agentMemoAta: userAMemoAta,
+          agentAccount,
+          pairAccount: derivePairPDA()[0],
+          authority: admin.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
@@ -387,6 +671,8 @@ describe("unified-token-program", () => {
       const connection = await program.account.connectionAccount.fetch(connectionPDA);
       assert.equal(connection.userAUnlocked, true);
       assert.equal(connection.userBUnlocked, false); // User B hasn't unlocked yet
+      assert.deepEqual(connection.state, { partiallyUnlocked: {} });
+      assert.equal(connection.userAFailedAttempts, 0, "Unlocking resets the attempt counter");
 
       // Verify MEMO reward (8 tokens)
       const memoAfterAccount = await getAccount(provider.connection, userAMemoAta);
@@ -403,20 +689,23 @@ describe("unified-token-program", () => {
 
     it("Should fail to unlock twice", async () => {
       const [userAPDA] = deriveUserPDA(userAId);
-      const userAMemoAta = await getAssociatedTokenAddress(memoMint, admin.publicKey);
+      const userAMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
 
       const pinToSubmit = Buffer.from([0x35, 0x36, 0x37, 0x38]);
 
       try {
         await program.methods
-          .unlockConnection(Array.from(pinToSubmit))
+          .unlockConnection(Array.from(hashUserId(userAId)), Array.from(pinToSubmit), Array.from(saltB))
           .accounts({
             connectionAccount: connectionPDA,
             userAccount: userAPDA,
             userMemoAta: userAMemoAta,
             globalState,
             memoMint,
-            payer: admin.publicKey,
+            agentMemoAta: userAMemoAta,
+            agentAccount,
+            pairAccount: derivePairPDA()[0],
+            authority: admin.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
           })
           .rpc();
@@ -427,9 +716,860 @@ describe("unified-token-program", () => {
         console.log("✓ Correctly rejected double unlock");
       }
     });
+
+    it("Should complete the connection when User B unlocks and pay the agent", async () => {
+      // Admin is the agent, and its MEMO account is User A's
+      const agentMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
+      const agentMemoBefore = await balanceOf(agentMemoAta);
+      const { config } = await program.account.globalState.fetch(globalState);
+
+      await unlockAs(connectionPDA, userBId, pinA, saltA);
+
+      const connection = await program.account.connectionAccount.fetch(connectionPDA);
+      assert.deepEqual(connection.state, { completed: {} });
+      assert.equal(connection.agentRewarded, true);
+
+      assert.equal(
+        (await balanceOf(agentMemoAta)) - agentMemoBefore,
+        config.agentMemoReward.toNumber(),
+        "Agent should receive agent_memo_reward"
+      );
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.connectionsCreated.toNumber(), 1);
+      assert.equal(agent.connectionsCompleted.toNumber(), 1);
+
+      const pair = await program.account.pairAccount.fetch(derivePairPDA()[0]);
+      assert.equal(pair.connectionsCompleted.toNumber(), 1);
+      assert.equal(pair.openConnections.toNumber(), 0);
+
+      console.log(`✓ Connection completed by User B; agent paid ${config.agentMemoReward.toNumber()} MEMO`);
+    });
+
+    it("Should not pay the agent again on a repeated unlock", async () => {
+      const agentMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
+      const agentMemoBefore = await balanceOf(agentMemoAta);
+
+      try {
+        await unlockAs(connectionPDA, userBId, pinA, saltA);
+        assert.fail("Should have failed - connection already completed");
+      } catch (error) {
+        assert.include(error.toString(), "InvalidConnectionState");
+      }
+
+      assert.equal(await balanceOf(agentMemoAta), agentMemoBefore);
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.connectionsCompleted.toNumber(), 1);
+
+      console.log("✓ Repeated unlock rejected; agent paid once");
+    });
+
+    it("Should refuse a new connection for the pair during the cooldown", async () => {
+      try {
+        await proposeConnection("alice123-bob456-2", { pinA, saltA, pinB, saltB }, 7 * 86400, 1);
+        assert.fail("Should have failed - pair cooldown active");
+      } catch (error) {
+        assert.include(error.toString(), "PairCooldownActive");
+      }
+
+      // Lift the cooldown so the remaining sections can reuse the pair
+      const { config } = await program.account.globalState.fetch(globalState);
+      await program.methods
+        .updateConfig({ ...config, pairCooldown: new BN(0) })
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+
+      console.log("✓ Repeat connection rejected during the pair cooldown");
+    });
+
+    it("Should lock a side out after config.max_pin_attempts wrong PINs", async () => {
+      const pins = { pinA: "4321", saltA: randomBytes(32), pinB: "8765", saltB: randomBytes(32) };
+      const connection = await proposeConnection("lockout-test", pins, 3600, 1);
+      await acceptAsUserB(connection);
+
+      const { config } = await program.account.globalState.fetch(globalState);
+      await program.methods
+        .updateConfig({ ...config, maxPinAttempts: 2 })
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+
+      try {
+        await unlockAs(connection, userBId, "0000", pins.saltA);
+        let account = await program.account.connectionAccount.fetch(connection);
+        assert.equal(account.userBFailedAttempts, 1);
+        assert.equal(account.userBLockedUntil.toNumber(), 0);
+
+        await unlockAs(connection, userBId, "0000", pins.saltA);
+        account = await program.account.connectionAccount.fetch(connection);
+        assert.equal(account.userBFailedAttempts, 0, "Counter resets when the lockout starts");
+        assert.isAbove(account.userBLockedUntil.toNumber(), Math.floor(Date.now() / 1000));
+
+        try {
+          await unlockAs(connection, userBId, pins.pinA, pins.saltA);
+          assert.fail("Should have failed - locked out");
+        } catch (error) {
+          assert.include(error.toString(), "PinLockedOut");
+        }
+
+        console.log("✓ User B locked out, even with the correct PIN");
+      } finally {
+        await program.methods
+          .updateConfig(config)
+          .accounts({ globalState, admin: admin.publicKey })
+          .rpc();
+        await program.methods
+          .cancelConnection(
+            Array.from(hashUserId(userAId)),
+            Array.from(hashUserId(userBId)),
+            Array.from(hashUserId(userAId))
+          )
+          .accounts({
+            connectionAccount: connection,
+            userAAccount: deriveUserPDA(userAId)[0],
+            userBAccount: deriveUserPDA(userBId)[0],
+            userAMeMint: deriveMeMintPDA(userAId)[0],
+            userBMeMint: deriveMeMintPDA(userBId)[0],
+            userAMeAta: deriveUserTokenPDA("user_me_token", userAId)[0],
+            userBMeAta: deriveUserTokenPDA("user_me_token", userBId)[0],
+            pairAccount: derivePairPDA()[0],
+            globalState,
+            authority: admin.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      }
+    });
+  });
+
+  describe("7. Expire and Settle No-Show", () => {
+    const pins = { pinA: "2468", saltA: randomBytes(32), pinB: "1357", saltB: randomBytes(32) };
+    const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
+    const userBMeAta = deriveUserTokenPDA("user_me_token", userBId)[0];
+
+    const expireAccounts = (connection: PublicKey) => ({
+      connectionAccount: connection,
+      userAAccount: deriveUserPDA(userAId)[0],
+      userBAccount: deriveUserPDA(userBId)[0],
+      userAMeAta,
+      userBMeAta,
+      pairAccount: derivePairPDA()[0],
+      globalState,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    it("Should expire a lapsed connection and return both stakes", async () => {
+      const connection = await proposeConnection("expire-test", pins, 3600, 1);
+      await acceptAsUserB(connection);
+
+      try {
+        await program.methods
+          .expireConnection(Array.from(hashUserId(userAId)), Array.from(hashUserId(userBId)))
+          .accounts(expireAccounts(connection))
+          .rpc();
+        assert.fail("Should have failed - deadline not reached");
+      } catch (error) {
+        assert.include(error.toString(), "ConnectionNotExpired");
+      }
+
+      // Pull the deadline in to a few seconds from now and let it pass
+      await scheduleConnection(connection, 5, 6);
+      await sleep(10_000);
+
+      const balanceABefore = await balanceOf(userAMeAta);
+      const balanceBBefore = await balanceOf(userBMeAta);
+
+      await program.methods
+        .expireConnection(Array.from(hashUserId(userAId)), Array.from(hashUserId(userBId)))
+        .accounts(expireAccounts(connection))
+        .rpc();
+
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { expired: {} });
+      assert.equal((await balanceOf(userAMeAta)) - balanceABefore, 1);
+      assert.equal((await balanceOf(userBMeAta)) - balanceBBefore, 1);
+
+      console.log("✓ Lapsed connection expired and both stakes returned");
+    });
+
+    it("Should settle a no-show, paying the present user and burning the rest", async () => {
+      const connection = await proposeConnection("no-show-test", pins, 3600, 2);
+      await acceptAsUserB(connection);
+      await scheduleConnection(connection, 5, 8);
+
+      // User A shows up and unlocks with User B's PIN; User B never does
+      await unlockAs(connection, userAId, pins.pinB, pins.saltB);
+
+      const [userBMeMint] = deriveMeMintPDA(userBId);
+      const forfeitRecipient = await createAccount(
+        provider.connection,
+        admin.payer,
+        userBMeMint,
+        admin.publicKey,
+        Keypair.generate()
+      );
+      const settleAccounts = {
+        connectionAccount: connection,
+        presentUserAccount: deriveUserPDA(userAId)[0],
+        absentUserAccount: deriveUserPDA(userBId)[0],
+        absentMeMint: userBMeMint,
+        forfeitRecipient,
+        pairAccount: derivePairPDA()[0],
+        globalState,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      };
+
+      try {
+        await program.methods
+          .settleNoShow(Array.from(hashUserId(userAId)), Array.from(hashUserId(userBId)))
+          .accounts(settleAccounts)
+          .rpc();
+        assert.fail("Should have failed - deadline not reached");
+      } catch (error) {
+        assert.include(error.toString(), "ConnectionNotExpired");
+      }
+
+      await sleep(10_000);
+
+      const supplyBefore = Number((await getMint(provider.connection, userBMeMint)).supply);
+      await program.methods
+        .settleNoShow(Array.from(hashUserId(userAId)), Array.from(hashUserId(userBId)))
+        .accounts(settleAccounts)
+        .rpc();
+
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { expired: {} });
+
+      // Default no_show_forfeit_bps: half of B's 2 ME to A, half burned
+      assert.equal(await balanceOf(forfeitRecipient), 1);
+      const supplyAfter = Number((await getMint(provider.connection, userBMeMint)).supply);
+      assert.equal((supplyBefore - supplyAfter) / 1e9, 1);
+
+      console.log("✓ No-show settled: 1 ME to User A, 1 ME burned");
+    });
+  });
+
+  describe("8. Withdraw Locked ME", () => {
+    const userIdHash = Array.from(hashUserId(userAId));
+    const [userAPDA] = deriveUserPDA(userAId);
+    const [userAMeMint] = deriveMeMintPDA(userAId);
+    const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
+    const userAMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
+    const [meEscrow] = PublicKey.findProgramAddressSync(
+      [Buffer.from("me_escrow"), userAMeMint.toBuffer()],
+      program.programId
+    );
+    // The 7-day position opened in section 5; maturing it is out of reach
+    // of a test run, so only the early path is covered here
+    const [lockPosition] = PublicKey.findProgramAddressSync(
+      [Buffer.from("lock_position"), userAPDA.toBuffer(), new BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const withdrawAccounts = (authority: PublicKey) => ({
+      userAccount: userAPDA,
+      lockPosition,
+      userMeAta: userAMeAta,
+      userMemoAta: userAMemoAta,
+      globalState,
+      memoMint,
+      meEscrow,
+      meMint: userAMeMint,
+      authority,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    it("Should refuse a withdrawal not signed by the user's authority", async () => {
+      const stranger = Keypair.generate();
+      try {
+        await program.methods
+          .withdrawLockedMe(userIdHash)
+          .accounts(withdrawAccounts(stranger.publicKey))
+          .signers([stranger])
+          .rpc();
+        assert.fail("Should have failed - wrong authority");
+      } catch (error) {
+        assert.include(error.toString(), "UnauthorizedAuthority");
+        console.log("✓ Correctly rejected a stranger's withdrawal");
+      }
+    });
+
+    it("Should withdraw early, burning the position's MEMO and the ME penalty", async () => {
+      const meBefore = await balanceOf(userAMeAta);
+      const memoBefore = await balanceOf(userAMemoAta);
+      const userBefore = await program.account.userAccount.fetch(userAPDA);
+
+      await program.methods
+        .withdrawLockedMe(userIdHash)
+        .accounts(withdrawAccounts(admin.publicKey))
+        .rpc();
+
+      // Default early_withdrawal_penalty_bps burns 1 of the 10 ME
+      assert.equal((await balanceOf(userAMeAta)) - meBefore, 9);
+      assert.equal(memoBefore - (await balanceOf(userAMemoAta)), 10);
+      assert.equal(await balanceOf(meEscrow), 0);
+
+      const userAfter = await program.account.userAccount.fetch(userAPDA);
+      assert.equal(userAfter.totalMeLocked.toNumber(), 0);
+      assert.equal(userBefore.totalMemoEarned.toNumber() - userAfter.totalMemoEarned.toNumber(), 10);
+      assert.isNull(await program.account.lockPosition.fetchNullable(lockPosition));
+
+      console.log("✓ Early withdrawal returned 9 ME and burned 10 MEMO");
+    });
+  });
+
+  describe("9. Admin Controls", () => {
+    const newAdmin = Keypair.generate();
+    const coSigner = Keypair.generate();
+
+    it("Should update the config and reject out-of-range values", async () => {
+      const { config } = await program.account.globalState.fetch(globalState);
+
+      try {
+        await program.methods
+          .updateConfig({ ...config, dailyMeLimit: new BN(0) })
+          .accounts({ globalState, admin: admin.publicKey })
+          .rpc();
+        assert.fail("Should have failed - daily limit out of range");
+      } catch (error) {
+        assert.include(error.toString(), "InvalidConfig");
+      }
+
+      await program.methods
+        .updateConfig({ ...config, connectionMemoReward: new BN(10) })
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+      let global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.config.connectionMemoReward.toNumber(), 10);
+
+      await program.methods
+        .updateConfig(config)
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+      global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.config.connectionMemoReward.toNumber(), 8);
+
+      console.log("✓ Config updated, bad values rejected");
+    });
+
+    it("Should transfer admin in two steps and back", async () => {
+      await program.methods
+        .proposeAdmin(newAdmin.publicKey)
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+
+      // Only the proposed key can accept
+      const stranger = Keypair.generate();
+      try {
+        await program.methods
+          .acceptAdmin()
+          .accounts({ globalState, newAdmin: stranger.publicKey })
+          .signers([stranger])
+          .rpc();
+        assert.fail("Should have failed - not the pending admin");
+      } catch (error) {
+        assert.include(error.toString(), "NotPendingAdmin");
+      }
+
+      await program.methods
+        .acceptAdmin()
+        .accounts({ globalState, newAdmin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc();
+      let global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.admin.toString(), newAdmin.publicKey.toString());
+      assert.equal(global.pendingAdmin.toString(), PublicKey.default.toString());
+
+      // The old admin has lost its rights
+      try {
+        await program.methods
+          .proposeAdmin(admin.publicKey)
+          .accounts({ globalState, admin: admin.publicKey })
+          .rpc();
+        assert.fail("Should have failed - no longer admin");
+      } catch (error) {
+        assert.include(error.toString(), "UnauthorizedAdmin");
+      }
+
+      await program.methods
+        .proposeAdmin(admin.publicKey)
+        .accounts({ globalState, admin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc();
+      await program.methods
+        .acceptAdmin()
+        .accounts({ globalState, newAdmin: admin.publicKey })
+        .rpc();
+      global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.admin.toString(), admin.publicKey.toString());
+
+      console.log("✓ Admin transferred and returned");
+    });
+
+    it("Should require the multisig threshold once a multisig is set", async () => {
+      const coSignerAccount = { pubkey: coSigner.publicKey, isSigner: true, isWritable: false };
+
+      await program.methods
+        .setAdminMultisig([admin.publicKey, coSigner.publicKey], 2)
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+
+      try {
+        await program.methods
+          .setGuardian(PublicKey.default)
+          .accounts({ globalState, admin: admin.publicKey })
+          .rpc();
+        assert.fail("Should have failed - one of two signatures");
+      } catch (error) {
+        assert.include(error.toString(), "UnauthorizedAdmin");
+      }
+
+      // Two of two: remove the multisig again, returning control to the admin key
+      await program.methods
+        .setAdminMultisig([], 0)
+        .accounts({ globalState, admin: admin.publicKey })
+        .remainingAccounts([coSignerAccount])
+        .signers([coSigner])
+        .rpc();
+      const global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.adminMultisig.threshold, 0);
+
+      console.log("✓ Multisig threshold enforced");
+    });
+  });
+
+  describe("10. Emergency Pause", () => {
+    const guardian = Keypair.generate();
+    const PAUSE_CLAIM_ME = 1 << 1;
+
+    const claimAsUserA = () =>
+      program.methods
+        .claimMe(Array.from(hashUserId(userAId)))
+        .accounts({
+          userAccount: deriveUserPDA(userAId)[0],
+          meMint: deriveMeMintPDA(userAId)[0],
+          userMeAta: deriveUserTokenPDA("user_me_token", userAId)[0],
+          globalState,
+          authority: admin.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    before("Appoint a guardian", async () => {
+      await program.methods
+        .setGuardian(guardian.publicKey)
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+    });
+
+    after("Remove the guardian", async () => {
+      await program.methods
+        .setGuardian(PublicKey.default)
+        .accounts({ globalState, admin: admin.publicKey })
+        .rpc();
+    });
+
+    it("Should let the guardian pause an instruction", async () => {
+      await program.methods
+        .pause(PAUSE_CLAIM_ME)
+        .accounts({ globalState, authority: guardian.publicKey })
+        .signers([guardian])
+        .rpc();
+
+      const global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.pauseFlags, PAUSE_CLAIM_ME);
+
+      try {
+        await claimAsUserA();
+        assert.fail("Should have failed - claim_me is paused");
+      } catch (error) {
+        assert.include(error.toString(), "InstructionPaused");
+      }
+
+      console.log("✓ Guardian paused claim_me");
+    });
+
+    it("Should not let the guardian unpause", async () => {
+      try {
+        await program.methods
+          .unpause(PAUSE_CLAIM_ME)
+          .accounts({ globalState, authority: guardian.publicKey })
+          .signers([guardian])
+          .rpc();
+        assert.fail("Should have failed - guardian cannot unpause");
+      } catch (error) {
+        assert.include(error.toString(), "UnauthorizedAdmin");
+      }
+
+      const global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.pauseFlags, PAUSE_CLAIM_ME);
+
+      console.log("✓ Guardian unpause rejected");
+    });
+
+    it("Should let the admin unpause", async () => {
+      await program.methods
+        .unpause(PAUSE_CLAIM_ME)
+        .accounts({ globalState, authority: admin.publicKey })
+        .rpc();
+
+      const global = await program.account.globalState.fetch(globalState);
+      assert.equal(global.pauseFlags, 0);
+
+      // claim_me runs again, and only fails because nothing has accrued yet
+      try {
+        await claimAsUserA();
+        assert.fail("Should have failed with nothing to claim");
+      } catch (error) {
+        assert.include(error.toString(), "NothingToClaim");
+      }
+
+      console.log("✓ Admin unpaused claim_me");
+    });
+  });
+
+  describe("11. Agent Bonds", () => {
+    // Admin's MEMO sits in User A's MEMO account, which admin owns
+    const agentMemoAta = deriveUserTokenPDA("user_memo_token", userAId)[0];
+    const [agentBondVault] = PublicKey.findProgramAddressSync(
+      [Buffer.from("agent_bond"), admin.publicKey.toBuffer()],
+      program.programId
+    );
+
+    it("Should bond MEMO into the agent's vault", async () => {
+      await program.methods
+        .bondAgent(new BN(5))
+        .accounts({
+          agentAccount,
+          agentBondVault,
+          agentMemoAta,
+          globalState,
+          memoMint,
+          agent: admin.publicKey,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.bondedAmount.toNumber(), 5);
+      assert.equal(await balanceOf(agentBondVault), 5);
+
+      console.log("✓ Agent bonded 5 MEMO");
+    });
+
+    it("Should hold unbonded MEMO until the cooldown passes", async () => {
+      await program.methods
+        .requestUnbond(new BN(2))
+        .accounts({ agentAccount, globalState, agent: admin.publicKey })
+        .rpc();
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.bondedAmount.toNumber(), 3);
+      assert.equal(agent.unbondingAmount.toNumber(), 2);
+
+      try {
+        await program.methods
+          .withdrawUnbonded()
+          .accounts({
+            agentAccount,
+            agentBondVault,
+            agentMemoAta,
+            globalState,
+            agent: admin.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+        assert.fail("Should have failed - cooldown active");
+      } catch (error) {
+        assert.include(error.toString(), "UnbondCooldownActive");
+      }
+
+      console.log("✓ Unbonding MEMO held during the cooldown");
+    });
+
+    it("Should slash the bond, then unbonding MEMO, and burn it", async () => {
+      const slashAccounts = {
+        agentAccount,
+        agentBondVault,
+        globalState,
+        memoMint,
+        admin: admin.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      };
+
+      await program.methods.slashAgent(new BN(4)).accounts(slashAccounts).rpc();
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.bondedAmount.toNumber(), 0);
+      assert.equal(agent.unbondingAmount.toNumber(), 1);
+      assert.equal(agent.totalSlashed.toNumber(), 4);
+      assert.equal(await balanceOf(agentBondVault), 1);
+
+      try {
+        await program.methods.slashAgent(new BN(2)).accounts(slashAccounts).rpc();
+        assert.fail("Should have failed - more than the agent holds");
+      } catch (error) {
+        assert.include(error.toString(), "InsufficientAgentBond");
+      }
+
+      console.log("✓ Agent slashed 4 MEMO; over-slash rejected");
+    });
+  });
+
+  describe("12. Decline Connection", () => {
+    const pins = { pinA: "1111", saltA: randomBytes(32), pinB: "2222", saltB: randomBytes(32) };
+    const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
+
+    it("Should let User B decline without User A's stake ever leaving their account", async () => {
+      const balanceBefore = await balanceOf(userAMeAta);
+      const connection = await proposeConnection("decline-test", pins, 3600, 2);
+      assert.equal(await balanceOf(userAMeAta), balanceBefore);
+
+      await program.methods
+        .declineConnection(Array.from(hashUserId(userBId)))
+        .accounts({
+          connectionAccount: connection,
+          userBAccount: deriveUserPDA(userBId)[0],
+          pairAccount: derivePairPDA()[0],
+          globalState,
+          userBAuthority: admin.publicKey,
+        })
+        .rpc();
+
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { cancelled: {} });
+      assert.equal(await balanceOf(userAMeAta), balanceBefore);
+
+      try {
+        await acceptAsUserB(connection);
+        assert.fail("Should have failed - connection was declined");
+      } catch (error) {
+        assert.include(error.toString(), "InvalidConnectionState");
+      }
+
+      console.log("✓ Declined connection escrowed nothing and can no longer be accepted");
+    });
+  });
+
+  describe("13. Cancel and Reschedule", () => {
+    const pins = { pinA: "1111", saltA: randomBytes(32), pinB: "2222", saltB: randomBytes(32) };
+    const userAMeAta = deriveUserTokenPDA("user_me_token", userAId)[0];
+    const userBMeAta = deriveUserTokenPDA("user_me_token", userBId)[0];
+
+    // User C holds their own wallet, so User A's and User C's authorities differ
+    const userCId = "telegram:carol789";
+    const userCAuthority = Keypair.generate();
+    const userCMeAta = deriveUserTokenPDA("user_me_token", userCId)[0];
+
+    let penaltyBps: number;
+
+    // Helper function: Cancel a connection between User A and `otherId` on behalf
+    // of `cancellerId` (User A by default), signed by `authority`
+    async function cancelAs(
+      connection: PublicKey,
+      otherId: string,
+      authority?: Keypair,
+      cancellerId: string = userAId
+    ) {
+      return program.methods
+        .cancelConnection(
+          Array.from(hashUserId(userAId)),
+          Array.from(hashUserId(otherId)),
+          Array.from(hashUserId(cancellerId))
+        )
+        .accounts({
+          connectionAccount: connection,
+          userAAccount: deriveUserPDA(userAId)[0],
+          userBAccount: deriveUserPDA(otherId)[0],
+          userAMeMint: deriveMeMintPDA(userAId)[0],
+          userBMeMint: deriveMeMintPDA(otherId)[0],
+          userAMeAta,
+          userBMeAta: deriveUserTokenPDA("user_me_token", otherId)[0],
+          pairAccount: derivePairPDA(otherId)[0],
+          globalState,
+          authority: authority ? authority.publicKey : admin.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers(authority ? [authority] : [])
+        .rpc();
+    }
+
+    before("Register User C with their own authority", async () => {
+      await program.methods
+        .initializeUser(userCId, Array.from(hashUserId(userCId)))
+        .accounts({
+          userAccount: deriveUserPDA(userCId)[0],
+          meMint: deriveMeMintPDA(userCId)[0],
+          userMeAta: userCMeAta,
+          userMemoAta: deriveUserTokenPDA("user_memo_token", userCId)[0],
+          globalState,
+          memoMint,
+          authority: userCAuthority.publicKey,
+          payer: admin.publicKey,
+          systemProgram: SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          rent: SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+
+      const global = await program.account.globalState.fetch(globalState);
+      penaltyBps = global.config.cancelPenaltyBps.toNumber();
+    });
+
+    it("Should refund a proposal cancelled before acceptance in full", async () => {
+      const balanceBefore = await balanceOf(userAMeAta);
+      const connection = await proposeConnection("cancel-proposed", pins, 3600, 2);
+
+      await cancelAs(connection, userBId);
+
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { cancelled: {} });
+      assert.equal(await balanceOf(userAMeAta), balanceBefore);
+
+      console.log("✓ Proposal cancelled with no penalty");
+    });
+
+    it("Should burn the canceller's penalty when User A cancels after acceptance", async () => {
+      const aBefore = await balanceOf(userAMeAta);
+      const bBefore = await balanceOf(userBMeAta);
+      const aSupplyBefore = Number((await getMint(provider.connection, deriveMeMintPDA(userAId)[0])).supply);
+
+      const connection = await proposeConnection("cancel-accepted", pins, 3600, 2);
+      await acceptAsUserB(connection);
+      await cancelAs(connection, userBId);
+
+      const penalty = (2 * penaltyBps) / 10_000;
+      assert.closeTo(aBefore - (await balanceOf(userAMeAta)), penalty, 1e-9);
+      assert.equal(await balanceOf(userBMeAta), bBefore);
+      const aSupplyAfter = Number((await getMint(provider.connection, deriveMeMintPDA(userAId)[0])).supply);
+      assert.closeTo((aSupplyBefore - aSupplyAfter) / 1e9, penalty, 1e-9);
+
+      console.log(`✓ User A cancelled and forfeited ${penalty} ME; User B refunded in full`);
+    });
+
+    it("Should penalise User B, not User A, when B cancels under a shared authority", async () => {
+      // Admin is the authority of both users, so only the canceller hash tells them apart
+      const aBefore = await balanceOf(userAMeAta);
+      const bBefore = await balanceOf(userBMeAta);
+
+      const connection = await proposeConnection("cancel-by-b", pins, 3600, 2);
+      await acceptAsUserB(connection);
+      await cancelAs(connection, userBId, undefined, userBId);
+
+      const penalty = (2 * penaltyBps) / 10_000;
+      assert.equal(await balanceOf(userAMeAta), aBefore);
+      assert.closeTo(bBefore - (await balanceOf(userBMeAta)), penalty, 1e-9);
+
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { cancelled: {} });
+
+      console.log(`✓ User B cancelled and forfeited ${penalty} ME; User A refunded in full`);
+    });
+
+    it("Should penalise User C, not User A, when User C cancels", async () => {
+      const aBefore = await balanceOf(userAMeAta);
+      const cBefore = await balanceOf(userCMeAta);
+
+      const connection = await proposeConnection("cancel-by-c", pins, 3600, 2, userCId);
+      await acceptAsUserB(connection, userCId, userCAuthority);
+      await cancelAs(connection, userCId, userCAuthority, userCId);
+
+      const penalty = (2 * penaltyBps) / 10_000;
+      assert.equal(await balanceOf(userAMeAta), aBefore);
+      assert.closeTo(cBefore - (await balanceOf(userCMeAta)), penalty, 1e-9);
+
+      console.log(`✓ User C cancelled and forfeited ${penalty} ME; User A refunded in full`);
+    });
+
+    it("Should reject a cancel from an unrelated authority or of a cancelled connection", async () => {
+      const connection = await proposeConnection("cancel-rejects", pins, 3600, 1);
+
+      try {
+        await cancelAs(connection, userBId, Keypair.generate());
+        assert.fail("Should have failed - stranger is neither user's authority");
+      } catch (error) {
+        assert.include(error.toString(), "UnauthorizedAuthority");
+      }
+
+      await cancelAs(connection, userBId);
+
+      try {
+        await cancelAs(connection, userBId);
+        assert.fail("Should have failed - connection already cancelled");
+      } catch (error) {
+        assert.include(error.toString(), "InvalidConnectionState");
+      }
+
+      console.log("✓ Stranger and repeat cancels rejected");
+    });
+
+    it("Should reject a meeting window that ends before it starts", async () => {
+      const connection = await proposeConnection("reschedule-bad", pins, 3600, 1);
+      await acceptAsUserB(connection);
+
+      try {
+        await scheduleConnection(connection, 600, 300);
+        assert.fail("Should have failed - window ends before it starts");
+      } catch (error) {
+        assert.include(error.toString(), "InvalidMeetingWindow");
+      }
+
+      await scheduleConnection(connection, 300, 600);
+      const account = await program.account.connectionAccount.fetch(connection);
+      assert.deepEqual(account.state, { scheduled: {} });
+
+      await cancelAs(connection, userBId);
+
+      console.log("✓ Inverted meeting window rejected; valid window accepted");
+    });
+  });
+
+  describe("14. Agent Revocation", () => {
+    const pins = { pinA: "3333", saltA: randomBytes(32), pinB: "4444", saltB: randomBytes(32) };
+
+    it("Should refuse connections from a revoked agent until it is re-registered", async () => {
+      const before = await program.account.agentAccount.fetch(agentAccount);
+
+      await program.methods
+        .revokeAgent()
+        .accounts({ agentAccount, globalState, admin: admin.publicKey })
+        .rpc();
+
+      try {
+        let agent = await program.account.agentAccount.fetch(agentAccount);
+        assert.equal(agent.active, false);
+
+        try {
+          await proposeConnection("revoked-agent", pins, 3600, 1);
+          assert.fail("Should have failed - agent revoked");
+        } catch (error) {
+          assert.include(error.toString(), "AgentNotActive");
+        }
+
+        agent = await program.account.agentAccount.fetch(agentAccount);
+        assert.equal(agent.connectionsCreated.toNumber(), before.connectionsCreated.toNumber());
+      } finally {
+        await program.methods
+          .registerAgent(admin.publicKey)
+          .accounts({
+            agentAccount,
+            globalState,
+            admin: admin.publicKey,
+            payer: admin.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+      }
+
+      const agent = await program.account.agentAccount.fetch(agentAccount);
+      assert.equal(agent.active, true);
+      assert.equal(agent.connectionsCompleted.toNumber(), before.connectionsCompleted.toNumber());
+
+      console.log("✓ Revoked agent rejected; re-registration keeps its stats");
+    });
   });
 
-  describe("7. Summary", () => {
+  describe("15. Summary", () => {
     it("Should display final state", async () => {
       const [userAPDA] = deriveUserPDA(userAId);
       const [userBPDA] = deriveUserPDA(userBId);
@@ -6,7 +6,8 @@ declare_id!("11111111111111111111111111111111"); // Will be replaced when deploy
 pub mod user_pda {
     use super::*;
 
-    /// Create a PDA wallet for a user based on platform and user ID
+    /// Create a PDA wallet for a user based on platform and user ID.
+    /// `authority` is the key that signs for the user in other programs.
     pub fn create_user_wallet(
         ctx: Context<CreateUserWallet>,
         platform: String,
@@ -15,6 +16,7 @@ pub mod user_pda {
         let user_wallet = &mut ctx.accounts.user_wallet;
         user_wallet.platform = platform.clone();
         user_wallet.user_id = user_id.clone();
+        user_wallet.authority = ctx.accounts.authority.key();
         user_wallet.created_at = Clock::get()?.unix_timestamp;
         user_wallet.bump = ctx.bumps.user_wallet;
 
@@ -51,6 +53,10 @@ pub struct CreateUserWallet<'info> {
     )]
     pub user_wallet: Account<'info, UserWallet>,
 
+    /// CHECK: Wallet that will sign for the user.
+    /// May be a custodial agent key or the user's own wallet.
+    pub authority: UncheckedAccount<'info>,
+
     #[account(mut)]
     pub payer: Signer<'info>,
 
@@ -64,6 +70,7 @@ pub struct UpdateWallet<'info> {
         mut,
         seeds = [b"user", user_wallet.platform.as_bytes(), user_wallet.user_id.as_bytes()],
         bump = user_wallet.bump,
+        has_one = authority,
     )]
     pub user_wallet: Account<'info, UserWallet>,
 
@@ -75,6 +82,7 @@ pub struct UpdateWallet<'info> {
 pub struct UserWallet {
     pub platform: String,      // "telegram", "discord", etc. (32 bytes max)
     pub user_id: String,       // Platform-specific user ID (32 bytes max)
+    pub authority: Pubkey,     // Signs for this user
     pub created_at: i64,       // Unix timestamp
     pub updated_at: i64,       // Unix timestamp
     pub metadata: String,      // Optional metadata (64 bytes max)
@@ -82,9 +90,9 @@ pub struct UserWallet {
 }
 
 impl UserWallet {
-    // Calculate space: 8 (discriminator) + 32 (platform) + 32 (user_id) + 8 (created_at)
-    // + 8 (updated_at) + 64 (metadata) + 1 (bump) = 153
-    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8 + 64 + 1;
+    // Calculate space: 8 (discriminator) + 32 (platform) + 32 (user_id) + 32 (authority)
+    // + 8 (created_at) + 8 (updated_at) + 64 (metadata) + 1 (bump) = 185
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 64 + 1;
 }
 
 #[error_code]
@@ -14,7 +14,9 @@ export { SolanaService } from './service';
 // Export helper functions for PDA derivation
 export {
     hashUserId,
-    hashPin,
+    legacyHashUserId,
+    generatePinSalt,
+    commitPin,
     deriveGlobalStatePDA,
     deriveMemoMintPDA,
     deriveMeEscrowPDA,
@@ -23,6 +25,9 @@ export {
     deriveUserMeTokenPDA,
     deriveUserMemoTokenPDA,
     deriveConnectionPDA,
+    deriveStakeVaultPDA,
+    derivePairPDA,
+    deriveAgentPDA,
 } from './services/unifiedTokenService';
 
 // Export types
@@ -44,20 +44,8 @@ export const userTokensProvider: Provider = {
                 return { data: null, values: {}, text: '' };
             }
 
-            // Get agent's wallet public key (used to derive the user's token accounts)
-            // In agent-custodial model, token accounts are owned by agent wallet
-            const agentWallet = runtime.getSetting('SOLANA_PUBLIC_KEY');
-            if (!agentWallet) {
-                logger.debug('[userTokensProvider] No agent wallet configured');
-                return { data: null, values: {}, text: '' };
-            }
-
-            // Fetch user balances
-            // Note: getUserBalances is currently stubbed and needs implementation
-            const balances = await unifiedTokenService.getUserBalances(
-                userId,
-                agentWallet as any // PublicKey will be created in the service
-            );
+            // Fetch user balances from the user's PDA token accounts
+            const balances = await unifiedTokenService.getUserBalances(userId);
 
             if (!balances) {
                 logger.debug(`[userTokensProvider] No balances found for user ${userId}`);
@@ -72,7 +60,7 @@ export const userTokensProvider: Provider = {
             const values: Record<string, string> = {
                 me_balance: balances.meBalance.toFixed(2),
                 memo_balance: balances.memoBalance.toFixed(2),
-                daily_available: balances.dailyAvailable.toString(),
+                claimable_me: balances.claimableMe.toString(),
                 total_minted: balances.totalMinted.toString(),
                 total_locked: balances.totalLocked.toString(),
                 total_memo_earned: balances.totalMemoEarned.toString(),
@@ -83,7 +71,7 @@ export const userTokensProvider: Provider = {
             let text = `\n\nUser ${userId} Token Balances\n\n`;
             text += `$ME Tokens: ${values.me_balance}\n`;
             text += `$MEMO Tokens: ${values.memo_balance}\n`;
-            text += `Daily Available: ${values.daily_available} ME\n\n`;
+            text += `Claimable: ${values.claimable_me} ME\n\n`;
 
             text += `Stats:\n`;
             text += `- Total ME Minted: ${values.total_minted}\n`;
@@ -15,7 +15,7 @@ import {
     getAccount,
 } from '@solana/spl-token';
 import { BN } from '@coral-xyz/anchor';
-import { createHash } from 'crypto';
+import { createHash, randomBytes } from 'crypto';
 import { elizaLogger, Service, type IAgentRuntime } from '@elizaos/core';
 import { UNIFIED_TOKEN_SERVICE_NAME } from '../constants';
 import { getWalletKey } from '../keypairUtils';
@@ -28,21 +28,60 @@ const UNIFIED_TOKEN_PROGRAM_ID = new PublicKey('GXnod1W71vzjuFkXHxwQ2dkBe7t1auJM
 // Constants
 const INITIAL_ME_MINT = 48;
 const DAILY_ME_LIMIT = 24;
+const ME_CARRY_OVER_DAYS = 3;
+const DAY_IN_SECONDS = 86400;
 const CONNECTION_MEMO_REWARD = 8;
 const TOKEN_DECIMALS = 9;
 
+// Domain prefix of user ID hashes, shared by the unified program and me_token
+const USER_ID_DOMAIN = 'withparen:user_id:v1:';
+
+// Domain prefix of connection PIN commitments
+const PIN_COMMITMENT_DOMAIN = 'withparen:pin:v1';
+
 /**
- * Helper function to hash user ID
+ * Helper function to hash a platform-qualified user ID (e.g. "telegram:user123")
+ * under the programs' domain prefix. Every user PDA is derived from this hash.
  */
 export function hashUserId(userId: string): Buffer {
+    return createHash('sha256').update(USER_ID_DOMAIN).update(userId).digest();
+}
+
+/**
+ * Helper function to hash a user ID without the domain prefix. Only me_token
+ * user_me accounts registered before the prefix existed are keyed by it;
+ * HumanConnection still accepts it for them.
+ */
+export function legacyHashUserId(userId: string): Buffer {
     return createHash('sha256').update(userId).digest();
 }
 
 /**
- * Helper function to hash PIN
+ * Generate the 32-byte salt of a connection PIN. Run it on the PIN owner's
+ * device: the salt travels only with the PIN and is revealed on unlock.
+ */
+export function generatePinSalt(): Buffer {
+    return randomBytes(32);
+}
+
+/**
+ * Helper function to commit to a connection PIN:
+ * sha256(domain || salt || connection || pin owner's user account || pin).
+ * Mirrors `pin_commitment` in the unified program.
  */
-export function hashPin(pin: string): Buffer {
-    return createHash('sha256').update(pin).digest();
+export function commitPin(
+    salt: Buffer | Uint8Array,
+    connection: PublicKey,
+    owner: PublicKey,
+    pin: string
+): Buffer {
+    return createHash('sha256')
+        .update(PIN_COMMITMENT_DOMAIN)
+        .update(Buffer.from(salt))
+        .update(connection.toBuffer())
+        .update(owner.toBuffer())
+        .update(Buffer.from(pin, 'utf8'))
+        .digest();
 }
 
 /**
@@ -82,6 +121,14 @@ function encodeU64(num: number | BN): Buffer {
     return buffer;
 }
 
+/**
+ * Encode an i64 number (8 bytes, little-endian, two's complement)
+ */
+function encodeI64(num: number | BN): Buffer {
+    const bn = typeof num === 'number' ? new BN(num) : num;
+    return bn.toTwos(64).toArrayLike(Buffer, 'le', 8);
+}
+
 /**
  * Derive global state PDA
  */
@@ -103,11 +150,14 @@ export function deriveMemoMintPDA(programId: PublicKey = UNIFIED_TOKEN_PROGRAM_I
 }
 
 /**
- * Derive ME escrow PDA
+ * Derive the escrow PDA holding a user's locked ME
  */
-export function deriveMeEscrowPDA(programId: PublicKey = UNIFIED_TOKEN_PROGRAM_ID): [PublicKey, number] {
+export function deriveMeEscrowPDA(
+    meMint: PublicKey,
+    programId: PublicKey = UNIFIED_TOKEN_PROGRAM_ID
+): [PublicKey, number] {
     return PublicKey.findProgramAddressSync(
-        [Buffer.from('me_escrow')],
+        [Buffer.from('me_escrow'), meMint.toBuffer()],
         programId
     );
 }
@@ -181,10 +231,56 @@ export function deriveConnectionPDA(
     );
 }
 
+/**
+ * Derive a user's stake vault for a connection
+ */
+export function deriveStakeVaultPDA(
+    connection: PublicKey,
+    userAccount: PublicKey,
+    programId: PublicKey = UNIFIED_TOKEN_PROGRAM_ID
+): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from('stake_vault'), connection.toBuffer(), userAccount.toBuffer()],
+        programId
+    );
+}
+
+/**
+ * Derive the pair PDA shared by every connection between two users, whichever
+ * side is user A
+ */
+export function derivePairPDA(
+    userAAccount: PublicKey,
+    userBAccount: PublicKey,
+    programId: PublicKey = UNIFIED_TOKEN_PROGRAM_ID
+): [PublicKey, number] {
+    const [low, high] =
+        Buffer.compare(userAAccount.toBuffer(), userBAccount.toBuffer()) <= 0
+            ? [userAAccount, userBAccount]
+            : [userBAccount, userAAccount];
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from('pair'), low.toBuffer(), high.toBuffer()],
+        programId
+    );
+}
+
+/**
+ * Derive agent account PDA
+ */
+export function deriveAgentPDA(
+    agent: PublicKey,
+    programId: PublicKey = UNIFIED_TOKEN_PROGRAM_ID
+): [PublicKey, number] {
+    return PublicKey.findProgramAddressSync(
+        [Buffer.from('agent'), agent.toBuffer()],
+        programId
+    );
+}
+
 export interface UserBalances {
     meBalance: number;
     memoBalance: number;
-    dailyAvailable: number;
+    claimableMe: number;
     totalMinted: number;
     totalLocked: number;
     totalMemoEarned: number;
@@ -213,7 +309,6 @@ export class UnifiedTokenService extends Service {
     private programId: PublicKey;
     private globalState: PublicKey;
     private memoMint: PublicKey;
-    private meEscrow: PublicKey;
     private payerKeypair: Keypair | null = null;
     private initialized: boolean = false;
 
@@ -233,7 +328,6 @@ export class UnifiedTokenService extends Service {
         // Derive global PDAs
         [this.globalState] = deriveGlobalStatePDA(this.programId);
         [this.memoMint] = deriveMemoMintPDA(this.programId);
-        [this.meEscrow] = deriveMeEscrowPDA(this.programId);
 
         // Initialize keypair and program asynchronously
         this.initialize();
@@ -303,7 +397,6 @@ export class UnifiedTokenService extends Service {
             .accounts({
                 globalState: this.globalState,
                 memoMint: this.memoMint,
-                meEscrow: this.meEscrow,
                 admin: signer.publicKey,
                 systemProgram: SystemProgram.programId,
                 tokenProgram: TOKEN_PROGRAM_ID,
@@ -318,12 +411,14 @@ export class UnifiedTokenService extends Service {
     }
 
     /**
-     * Initialize a user with PDA, ME mint, and token accounts
-     * Mints initial 48 $ME tokens
+     * Initialize a user with PDA, ME mint, token accounts and ME escrow
+     * Mints initial 48 $ME tokens. `authority` controls the user and owns its
+     * token accounts; it defaults to the payer (agent-custodial).
      */
     async initializeUser(
         userId: string,
-        payerKeypair?: Keypair
+        payerKeypair?: Keypair,
+        authority?: PublicKey
     ): Promise<string> {
         await this.ensureInitialized();
         logger.info(`[UnifiedTokenService] Initializing user: ${userId}`);
@@ -341,6 +436,7 @@ export class UnifiedTokenService extends Service {
         // Derive PDA-based token accounts
         const [userMeAta] = deriveUserMeTokenPDA(userId, this.programId);
         const [userMemoAta] = deriveUserMemoTokenPDA(userId, this.programId);
+        const [meEscrow] = deriveMeEscrowPDA(meMint, this.programId);
 
         logger.debug(`[UnifiedTokenService] User Account: ${userAccount.toString()}`);
         logger.debug(`[UnifiedTokenService] ME Mint: ${meMint.toString()}`);
@@ -381,8 +477,10 @@ export class UnifiedTokenService extends Service {
             { pubkey: meMint, isSigner: false, isWritable: true },
             { pubkey: userMeAta, isSigner: false, isWritable: true },
             { pubkey: userMemoAta, isSigner: false, isWritable: true },
-            { pubkey: this.globalState, isSigner: false, isWritable: true },
+            { pubkey: meEscrow, isSigner: false, isWritable: true },
+            { pubkey: this.globalState, isSigner: false, isWritable: false },
             { pubkey: this.memoMint, isSigner: false, isWritable: false },
+            { pubkey: authority ?? signer.publicKey, isSigner: false, isWritable: false },
             { pubkey: signer.publicKey, isSigner: true, isWritable: true },
             { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
@@ -413,43 +511,57 @@ export class UnifiedTokenService extends Service {
     }
 
     /**
-     * Mint daily ME tokens (up to 24/day)
-     * TODO: Implement with manual instruction building (no Anchor Program)
+     * Claim the ME accrued since the last claim (24/day, up to 3 days carried over)
      */
-    async mintDailyMe(
+    async claimMe(
         userId: string,
-        payerKeypair?: Keypair
+        authorityKeypair?: Keypair
     ): Promise<string> {
         await this.ensureInitialized();
-        throw new Error('[UnifiedTokenService] mintDailyMe not yet implemented - requires manual instruction building');
-        /* TODO: Build instruction manually without Anchor
-        logger.info(`[UnifiedTokenService] Minting daily ME for user: ${userId}`);
+        logger.info(`[UnifiedTokenService] Claiming ME for user: ${userId}`);
 
-        const signer = payerKeypair || this.payerKeypair;
+        const signer = authorityKeypair || this.payerKeypair;
         if (!signer) {
-            throw new Error('No keypair available for minting');
+            throw new Error('No keypair available for claiming');
         }
 
-        const userIdHash = Array.from(hashUserId(userId));
+        const userIdHash = hashUserId(userId);
         const [userAccount] = deriveUserAccountPDA(userId, this.programId);
         const [meMint] = deriveMeMintPDA(userId, this.programId);
         const [userMeAta] = deriveUserMeTokenPDA(userId, this.programId);
 
-        const tx = await this.program.methods
-            .mintDailyMe(userId, userIdHash)
-            .accounts({
-                userAccount,
-                meMint,
-                userMeAta,
-                payer: signer.publicKey,
-                tokenProgram: TOKEN_PROGRAM_ID,
-            })
-            .signers([signer])
-            .rpc();
+        // Build instruction data: discriminator + userIdHash (u8[32])
+        const discriminator = getInstructionDiscriminator('claim_me');
+        const instructionData = Buffer.concat([discriminator, encodeU8Array32(userIdHash)]);
 
-        logger.info(`[UnifiedTokenService] ✓ Minted daily ME for ${userId}. Tx: ${tx}`);
-        return tx;
-        */
+        // Build accounts array (must match IDL order)
+        const keys = [
+            { pubkey: userAccount, isSigner: false, isWritable: true },
+            { pubkey: meMint, isSigner: false, isWritable: true },
+            { pubkey: userMeAta, isSigner: false, isWritable: true },
+            { pubkey: this.globalState, isSigner: false, isWritable: false },
+            { pubkey: signer.publicKey, isSigner: true, isWritable: false },
+            { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        ];
+
+        // Create instruction
+        const instruction = new TransactionInstruction({
+            keys,
+            programId: this.programId,
+            data: instructionData,
+        });
+
+        // Create and send transaction
+        const transaction = new Transaction().add(instruction);
+        const signature = await sendAndConfirmTransaction(
+            this.connection,
+            transaction,
+            [signer],
+            { commitment: 'confirmed' }
+        );
+
+        logger.info(`[UnifiedTokenService] ✓ Claimed ME for ${userId}. Tx: ${signature}`);
+        return signature;
     }
 
     /**
@@ -485,7 +597,7 @@ export class UnifiedTokenService extends Service {
                 userMemoAta,
                 globalState: this.globalState,
                 memoMint: this.memoMint,
-                meEscrow: this.meEscrow,
+                meEscrow: deriveMeEscrowPDA(meMint, this.programId)[0],
                 meMint,
                 payer: signer.publicKey,
                 tokenProgram: TOKEN_PROGRAM_ID,
@@ -499,14 +611,20 @@ export class UnifiedTokenService extends Service {
     }
 
     /**
-     * Create a connection between two users
+     * Create a connection between two users. Each PIN commitment is built by
+     * its owner with `commitPin`, so the agent never sees a PIN or its salt.
+     * User A's authority proposes; the service wallet signs as the brokering
+     * agent and pays for the accounts. Both stakes are escrowed on accept.
      */
     async createConnection(
         connectionId: string,
         userAId: string,
         userBId: string,
-        pinA: string,
-        pinB: string,
+        pinACommitment: Buffer,
+        pinBCommitment: Buffer,
+        expirySeconds: number,
+        stakeAmount: number,
+        userAAuthority: Keypair,
         payerKeypair?: Keypair
     ): Promise<string> {
         await this.ensureInitialized();
@@ -520,34 +638,45 @@ export class UnifiedTokenService extends Service {
         const [connectionAccount] = deriveConnectionPDA(connectionId, this.programId);
         const [userAAccount] = deriveUserAccountPDA(userAId, this.programId);
         const [userBAccount] = deriveUserAccountPDA(userBId, this.programId);
-
-        const pinAHash = Array.from(hashPin(pinA));
-        const pinBHash = Array.from(hashPin(pinB));
-
-        // Build instruction data: discriminator + connectionId + userAId + userBId + pinAHash + pinBHash
+        const [pairAccount] = derivePairPDA(userAAccount, userBAccount, this.programId);
+        const [userAMeMint] = deriveMeMintPDA(userAId, this.programId);
+        const [userBMeMint] = deriveMeMintPDA(userBId, this.programId);
+        const [stakeVaultA] = deriveStakeVaultPDA(connectionAccount, userAAccount, this.programId);
+        const [stakeVaultB] = deriveStakeVaultPDA(connectionAccount, userBAccount, this.programId);
+        const [agentAccount] = deriveAgentPDA(signer.publicKey, this.programId);
+
+        // Build instruction data: discriminator + connectionId + userAId + userBId + pinAHash + pinBHash +
+        //                         expirySeconds + stakeAmount
         const discriminator = getInstructionDiscriminator('create_connection');
-        const connectionIdEncoded = encodeString(connectionId);
-        const userAIdEncoded = encodeString(userAId);
-        const userBIdEncoded = encodeString(userBId);
-        const pinAHashEncoded = encodeU8Array32(pinAHash);
-        const pinBHashEncoded = encodeU8Array32(pinBHash);
         const instructionData = Buffer.concat([
             discriminator,
-            connectionIdEncoded,
-            userAIdEncoded,
-            userBIdEncoded,
-            pinAHashEncoded,
-            pinBHashEncoded,
+            encodeString(connectionId),
+            encodeString(userAId),
+            encodeString(userBId),
+            encodeU8Array32(pinACommitment),
+            encodeU8Array32(pinBCommitment),
+            encodeI64(expirySeconds),
+            encodeU64(stakeAmount),
         ]);
 
         // Build accounts array (must match IDL order)
         const keys = [
             { pubkey: connectionAccount, isSigner: false, isWritable: true },
             { pubkey: userAAccount, isSigner: false, isWritable: true },
-            { pubkey: userBAccount, isSigner: false, isWritable: true },
+            { pubkey: userBAccount, isSigner: false, isWritable: false },
+            { pubkey: pairAccount, isSigner: false, isWritable: true },
+            { pubkey: userAMeMint, isSigner: false, isWritable: false },
+            { pubkey: userBMeMint, isSigner: false, isWritable: false },
+            { pubkey: stakeVaultA, isSigner: false, isWritable: true },
+            { pubkey: stakeVaultB, isSigner: false, isWritable: true },
             { pubkey: this.globalState, isSigner: false, isWritable: true },
+            { pubkey: userAAuthority.publicKey, isSigner: true, isWritable: false },
+            { pubkey: signer.publicKey, isSigner: true, isWritable: false },
+            { pubkey: agentAccount, isSigner: false, isWritable: true },
             { pubkey: signer.publicKey, isSigner: true, isWritable: true },
             { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+            { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
         ];
 
         // Create instruction
@@ -562,7 +691,7 @@ export class UnifiedTokenService extends Service {
         const signature = await sendAndConfirmTransaction(
             this.connection,
             transaction,
-            [signer],
+            userAAuthority.publicKey.equals(signer.publicKey) ? [signer] : [signer, userAAuthority],
             { commitment: 'confirmed' }
         );
 
@@ -571,13 +700,17 @@ export class UnifiedTokenService extends Service {
     }
 
     /**
-     * Unlock a connection with PIN
+     * Unlock a connection with the other user's PIN and the salt it was
+     * committed with. The MEMO reward goes to the user; the agent's share goes
+     * to `agentMemoAta`, by default the agent's associated MEMO token account.
      */
     async unlockConnection(
         connectionId: string,
         userId: string,
         pin: string,
-        payerKeypair?: Keypair
+        salt: Buffer | Uint8Array,
+        payerKeypair?: Keypair,
+        agentMemoAta?: PublicKey
     ): Promise<{ signature: string; memoReward: number; bothUnlocked: boolean }> {
         await this.ensureInitialized();
         logger.info(`[UnifiedTokenService] Unlocking connection: ${connectionId} for user: ${userId}`);
@@ -590,7 +723,9 @@ export class UnifiedTokenService extends Service {
         // Derive all required PDAs
         const [connectionAccount] = deriveConnectionPDA(connectionId, this.programId);
         const [userAccount] = deriveUserAccountPDA(userId, this.programId);
+        const [userMeAta] = deriveUserMeTokenPDA(userId, this.programId);
         const [userMemoAta] = deriveUserMemoTokenPDA(userId, this.programId);
+        const [stakeVault] = deriveStakeVaultPDA(connectionAccount, userAccount, this.programId);
         const [globalState] = deriveGlobalStatePDA(this.programId);
         const [memoMint] = deriveMemoMintPDA(this.programId);
         const userIdHash = hashUserId(userId);
@@ -599,16 +734,35 @@ export class UnifiedTokenService extends Service {
         if (!/^\d{4}$/.test(pin)) {
             throw new Error('PIN must be exactly 4 digits');
         }
+        if (salt.length !== 32) {
+            throw new Error('PIN salt must be exactly 32 bytes');
+        }
+
+        // The agent and pair accounts are recorded on the connection
+        const connectionInfo = await this.connection.getAccountInfo(connectionAccount);
+        if (!connectionInfo) {
+            throw new Error(`Connection ${connectionId} not found`);
+        }
+        // ConnectionAccount layout: discriminator(8) + connection_id(64) + user_a(32) + user_b(32) +
+        //                           pin_a_hash(32) + pin_b_hash(32) + unlocked flags(2) + created_at(8) +
+        //                           expires_at(8) + state(1) + stake_amount(8) + failed attempts(2) +
+        //                           locked_until(16) + agent(32) + agent_rewarded(1) + pair(32) + ...
+        const agentOffset = 8 + 64 + 32 + 32 + 32 + 32 + 1 + 1 + 8 + 8 + 1 + 8 + 1 + 1 + 8 + 8;
+        const agent = new PublicKey(connectionInfo.data.subarray(agentOffset, agentOffset + 32));
+        const pairAccount = new PublicKey(connectionInfo.data.subarray(agentOffset + 33, agentOffset + 65));
+        const [agentAccount] = deriveAgentPDA(agent, this.programId);
+        const agentMemo = agentMemoAta ?? (await getAssociatedTokenAddress(memoMint, agent));
 
         // Convert PIN string to 4-byte array
-        const pinBytes = Buffer.from(pin, 'utf8').slice(0, 4);
+        const pinBytes = Buffer.from(pin, 'utf8').subarray(0, 4);
 
-        // Build instruction data: discriminator + user_id_hash + pin
+        // Build instruction data: discriminator + user_id_hash + pin + salt
         const discriminator = getInstructionDiscriminator('unlock_connection');
         const instructionData = Buffer.concat([
             discriminator,              // 8 bytes
             encodeU8Array32(userIdHash),  // 32 bytes
             pinBytes,                   // 4 bytes
+            encodeU8Array32(salt),      // 32 bytes
         ]);
 
         logger.debug(`[UnifiedTokenService] Instruction data length: ${instructionData.length} bytes`);
@@ -617,9 +771,14 @@ export class UnifiedTokenService extends Service {
         const keys = [
             { pubkey: connectionAccount, isSigner: false, isWritable: true },
             { pubkey: userAccount, isSigner: false, isWritable: true },
+            { pubkey: userMeAta, isSigner: false, isWritable: true },
             { pubkey: userMemoAta, isSigner: false, isWritable: true },
+            { pubkey: stakeVault, isSigner: false, isWritable: true },
             { pubkey: globalState, isSigner: false, isWritable: false },
             { pubkey: memoMint, isSigner: false, isWritable: true },
+            { pubkey: agentMemo, isSigner: false, isWritable: true },
+            { pubkey: agentAccount, isSigner: false, isWritable: true },
+            { pubkey: pairAccount, isSigner: false, isWritable: true },
             { pubkey: signer.publicKey, isSigner: true, isWritable: false },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
         ];
@@ -679,10 +838,9 @@ export class UnifiedTokenService extends Service {
     /**
      * Get user balances by fetching on-chain account data
      */
-    async getUserBalances(userId: string, walletAddress: PublicKey): Promise<UserBalances | null> {
+    async getUserBalances(userId: string): Promise<UserBalances | null> {
         try {
             const [userAccount] = deriveUserAccountPDA(userId, this.programId);
-            const [meMint] = deriveMeMintPDA(userId, this.programId);
 
             // Fetch user account data
             const accountInfo = await this.connection.getAccountInfo(userAccount);
@@ -692,8 +850,9 @@ export class UnifiedTokenService extends Service {
             }
 
             // Manually deserialize UserAccount struct
-            // Layout: user_id(64) + me_mint(32) + last_mint_time(8) + daily_minted_today(8) +
-            //         total_me_minted(8) + total_me_locked(8) + total_memo_earned(8) + connections_count(8) + bump(1)
+            // Layout: user_id(64) + authority(32) + me_mint(32) + last_claim_time(8) + total_me_minted(8) +
+            //         total_me_locked(8) + total_memo_earned(8) + connections_count(8) +
+            //         lock_positions_opened(8) + bump(1)
             // Note: First 8 bytes are Anchor discriminator
             const data = accountInfo.data;
             let offset = 8; // Skip Anchor discriminator
@@ -701,14 +860,14 @@ export class UnifiedTokenService extends Service {
             // Skip user_id (64 bytes)
             offset += 64;
 
-            // Skip me_mint (32 bytes)
+            // Skip authority (32 bytes)
             offset += 32;
 
-            // Skip last_mint_time (8 bytes)
-            offset += 8;
+            // Skip me_mint (32 bytes)
+            offset += 32;
 
-            // Read daily_minted_today (u64, 8 bytes, little-endian)
-            const dailyMinted = Number(data.readBigUInt64LE(offset));
+            // Read last_claim_time (i64, 8 bytes, little-endian)
+            const lastClaimTime = Number(data.readBigInt64LE(offset));
             offset += 8;
 
             // Read total_me_minted (u64, 8 bytes, little-endian)
@@ -726,9 +885,9 @@ export class UnifiedTokenService extends Service {
             // Read connections_count (u64, 8 bytes, little-endian)
             const connectionsCount = Number(data.readBigUInt64LE(offset));
 
-            // Get token balances from ATAs
-            const userMeAta = await getAssociatedTokenAddress(meMint, walletAddress);
-            const userMemoAta = await getAssociatedTokenAddress(this.memoMint, walletAddress);
+            // Get token balances from the user's PDA token accounts
+            const [userMeAta] = deriveUserMeTokenPDA(userId, this.programId);
+            const [userMemoAta] = deriveUserMemoTokenPDA(userId, this.programId);
 
             const meTokenAccount = await getAccount(this.connection, userMeAta);
             const memoTokenAccount = await getAccount(this.connection, userMemoAta);
@@ -737,13 +896,17 @@ export class UnifiedTokenService extends Service {
             const meBalance = Number(meTokenAccount.amount) / Math.pow(10, TOKEN_DECIMALS);
             const memoBalance = Number(memoTokenAccount.amount) / Math.pow(10, TOKEN_DECIMALS);
 
-            // Calculate daily available (24 - dailyMinted)
-            const dailyAvailable = DAILY_ME_LIMIT - dailyMinted;
+            // ME accrued since the last claim under the default config, as claim_me computes it
+            const elapsed = Math.max(0, Math.floor(Date.now() / 1000) - lastClaimTime);
+            const claimableMe = Math.min(
+                Math.floor((elapsed * DAILY_ME_LIMIT) / DAY_IN_SECONDS),
+                DAILY_ME_LIMIT * ME_CARRY_OVER_DAYS
+            );
 
             return {
                 meBalance,
                 memoBalance,
-                dailyAvailable: Math.max(0, dailyAvailable),
+                claimableMe,
                 totalMinted,
                 totalLocked,
                 totalMemoEarned,